@@ -5,4 +5,4 @@ export PRIVATE_KEY=$RELAYER_PRIVATE_KEY
 
 node init.js &
 
-./node-template --dev --ws-external --base-path /tmp/substrate
+./node-template --dev --ws-external --base-path /tmp/substrate --enable-offchain-indexing true
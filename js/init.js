@@ -11,8 +11,9 @@ async function initPallet(api, alice, owner) {
     console.log('Sending money to the pallet owner', owner.address);
     await initAccount(api, alice, owner.address);
 
-    // Set the current operator (relayer)
-    const setOperatorTx = api.tx.zeropoolOperatorManager.setOperator(owner.address);
+    // Register the owner as an operator (relayer)
+    const operatorInfo = { name: 'default', relayerUrl: process.env.RELAYER_URL || '', fee: { baseFee: 0, perByteFee: 0 } };
+    const setOperatorTx = api.tx.zeropoolOperatorManager.addOperator(owner.address, operatorInfo);
 
     // Set verification keys
     const transferVk = fs.readFileSync('params/transfer_verification_key.bin').toString('hex');
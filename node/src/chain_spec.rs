@@ -1,6 +1,6 @@
 use node_template_runtime::{
-    AccountId, AuraConfig, BalancesConfig, GenesisConfig, GrandpaConfig, Signature, SudoConfig,
-    SystemConfig, WASM_BINARY,
+    AccountId, AuraConfig, BalancesConfig, EnergyRewardAccount, ExistentialDeposit, GenesisConfig,
+    GrandpaConfig, Runtime, Signature, SudoConfig, SystemConfig, WASM_BINARY,
 };
 use sc_service::ChainType;
 use sp_consensus_aura::sr25519::AuthorityId as AuraId;
@@ -139,7 +139,18 @@ fn testnet_genesis(
         },
         balances: BalancesConfig {
             // Configure endowed accounts with initial balance of 1 << 60.
-            balances: endowed_accounts.iter().cloned().map(|k| (k, 1 << 60)).collect(),
+            balances: endowed_accounts
+                .iter()
+                .cloned()
+                .map(|k| (k, 1 << 60))
+                // The pool account must never be reaped, it holds the shielded funds.
+                .chain(core::iter::once((
+                    pallet_zeropool::Pallet::<Runtime>::account_id(),
+                    ExistentialDeposit::get(),
+                )))
+                // Pays the energy rewards.
+                .chain(core::iter::once((EnergyRewardAccount::get(), 1 << 60)))
+                .collect(),
         },
         aura: AuraConfig {
             authorities: initial_authorities.iter().map(|x| (x.0.clone())).collect(),
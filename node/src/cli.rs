@@ -7,6 +7,10 @@ pub struct Cli {
 
     #[clap(flatten)]
     pub run: RunCmd,
+
+    /// Index the pool transactions into the local database and serve them over RPC.
+    #[clap(long)]
+    pub zeropool_indexer: bool,
 }
 
 #[derive(Debug, clap::Subcommand)]
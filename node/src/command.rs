@@ -127,8 +127,9 @@ pub fn run() -> sc_cli::Result<()> {
             .into()),
         None => {
             let runner = cli.create_runner(&cli.run)?;
+            let zeropool_indexer = cli.zeropool_indexer;
             runner.run_node_until_exit(|config| async move {
-                service::new_full(config).map_err(sc_cli::Error::Service)
+                service::new_full(config, zeropool_indexer).map_err(sc_cli::Error::Service)
             })
         },
     }
@@ -0,0 +1,329 @@
+//! Indexer of the pool transactions.
+//!
+//! Follows the best chain and keeps the decoded pool transactions in the offchain database of
+//! the node, so the transaction history can be served without an external indexer. Blocks
+//! retracted by a reorg are rolled back using the tree route between the old and the new best
+//! block. Blocks whose state is already pruned are skipped, run an archive node to index the
+//! whole history.
+
+use std::sync::Arc;
+
+use codec::{Decode, Encode};
+use futures::{future, StreamExt};
+use node_template_runtime::{opaque::Block, Call, UncheckedExtrinsic};
+use pallet_zeropool::tx_decoder::{TxDecoder, TxType};
+use pallet_zeropool_runtime_api::{tx_index, ZeropoolApi};
+use sc_client_api::{Backend, BlockBackend, BlockchainEvents};
+use serde::{Deserialize, Serialize};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::{HashAndNumber, HeaderBackend, HeaderMetadata};
+use sp_core::{offchain::OffchainStorage, Bytes, U256};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+
+type Hash = <Block as BlockT>::Hash;
+
+const PREFIX: &[u8] = b"zeropool-indexer";
+const HEAD_KEY: &[u8] = b"head";
+
+/// Kind of a pool transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TxKind {
+    /// Deposit into the pool.
+    Deposit,
+    /// Transfer inside the pool.
+    Transfer,
+    /// Withdrawal from the pool.
+    Withdraw,
+}
+
+/// Fields of a pool transaction.
+#[derive(Clone, Debug, Encode, Decode, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedTx {
+    /// Kind of the transaction.
+    pub kind: TxKind,
+    /// Nullifier of the spent account.
+    pub nullifier: U256,
+    /// Pool index the transfer proof was made against.
+    pub transfer_index: U256,
+    /// Change of the energy, in the pool units.
+    pub energy_amount: U256,
+    /// Change of the token balance, in the pool units.
+    pub token_amount: U256,
+    /// Fee paid to the operator, in the pool units.
+    pub fee: U256,
+    /// Native amount requested by a withdrawal.
+    pub native_amount: Option<U256>,
+    /// Source of a deposit or destination of a withdrawal.
+    pub address: Option<Bytes>,
+    /// Encrypted notes and accounts.
+    pub memo: Bytes,
+}
+
+impl DecodedTx {
+    fn new(data: &[u8]) -> Self {
+        let tx = TxDecoder::new(data);
+        let kind = match tx.tx_type() {
+            TxType::Deposit => TxKind::Deposit,
+            TxType::Transfer => TxKind::Transfer,
+            TxType::Withdraw => TxKind::Withdraw,
+        };
+
+        DecodedTx {
+            kind,
+            nullifier: tx.nullifier().into(),
+            transfer_index: tx.transfer_index().into(),
+            energy_amount: tx.energy_amount().into(),
+            token_amount: tx.token_amount().into(),
+            fee: tx.memo_fee().into(),
+            native_amount: (kind == TxKind::Withdraw).then(|| tx.memo_native_amount().into()),
+            address: match kind {
+                TxKind::Deposit => Some(tx.deposit_address().to_vec().into()),
+                TxKind::Withdraw => Some(tx.memo_address().to_vec().into()),
+                TxKind::Transfer => None,
+            },
+            memo: tx.ciphertext().to_vec().into(),
+        }
+    }
+}
+
+/// A pool transaction included in the best chain.
+#[derive(Clone, Debug, Encode, Decode, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedTx {
+    /// Pool index of the transaction, the index of the first leaf of the commitment.
+    pub index: U256,
+    /// Hash of all the messages up to and including this transaction.
+    pub all_messages_hash: U256,
+    /// Output commitment of the transaction.
+    pub commitment: U256,
+    /// Block the transaction was included in.
+    pub block_hash: Hash,
+    /// Number of the block.
+    pub block_number: u32,
+    /// Index of the extrinsic in the block.
+    pub extrinsic_index: u32,
+    /// The decoded transaction. Missing if it wasn't submitted by a direct `transact` call.
+    pub tx: Option<DecodedTx>,
+}
+
+/// Last indexed block.
+#[derive(Encode, Decode)]
+struct Head {
+    hash: Hash,
+    /// Pool index after the block.
+    pool_index: U256,
+}
+
+fn tx_key(index: U256) -> Vec<u8> {
+    let mut key = b"tx::".to_vec();
+    let mut index_bytes = [0u8; 32];
+    index.to_big_endian(&mut index_bytes);
+    key.extend_from_slice(&index_bytes);
+    key
+}
+
+fn block_key(hash: &Hash) -> Vec<u8> {
+    [&b"block::"[..], hash.as_ref()].concat()
+}
+
+fn read<S: OffchainStorage, T: Decode>(storage: &S, key: &[u8]) -> Option<T> {
+    storage.get(PREFIX, key).and_then(|raw| T::decode(&mut &raw[..]).ok())
+}
+
+/// Returns up to `limit` indexed transactions starting from the transaction that `from_index`
+/// falls into. Only the next `limit` transactions are looked up, missing ones count against the
+/// limit.
+pub fn transactions<S: OffchainStorage>(
+    storage: &S,
+    from_index: U256,
+    limit: usize,
+) -> Vec<IndexedTx> {
+    let end = match read::<_, Head>(storage, HEAD_KEY) {
+        Some(head) => head.pool_index,
+        None => return Vec::new(),
+    };
+
+    let step = U256::from(128u32);
+    let mut index = tx_index(from_index);
+    let end = end.min(index.saturating_add(step * limit));
+    let mut txs = Vec::new();
+
+    while index < end && txs.len() < limit {
+        if let Some(tx) = read(storage, &tx_key(index)) {
+            txs.push(tx);
+        }
+        index += step;
+    }
+
+    txs
+}
+
+fn decode_transact(extrinsic: &<Block as BlockT>::Extrinsic) -> Option<DecodedTx> {
+    let extrinsic = UncheckedExtrinsic::decode(&mut &extrinsic.encode()[..]).ok()?;
+
+    match extrinsic.function {
+        Call::Zeropool(pallet_zeropool::Call::transact { data }) => Some(DecodedTx::new(&data)),
+        _ => None,
+    }
+}
+
+fn index_block<C, S>(
+    client: &C,
+    storage: &mut S,
+    block: &HashAndNumber<Block>,
+) -> sp_blockchain::Result<()>
+where
+    C: ProvideRuntimeApi<Block> + BlockBackend<Block>,
+    C::Api: ZeropoolApi<Block>,
+    S: OffchainStorage,
+{
+    let messages = match client.runtime_api().messages(&BlockId::hash(block.hash)) {
+        Ok(messages) => messages,
+        Err(err) => {
+            log::warn!("Skipping block {} in the zeropool indexer: {:?}", block.hash, err);
+            return Ok(())
+        },
+    };
+
+    if messages.is_empty() {
+        return Ok(())
+    }
+
+    let extrinsics = client.block_body(&BlockId::hash(block.hash))?.unwrap_or_default();
+    let mut indices = Vec::with_capacity(messages.len());
+
+    for message in messages {
+        let tx = IndexedTx {
+            index: message.index,
+            all_messages_hash: message.all_messages_hash,
+            commitment: message.commitment,
+            block_hash: block.hash,
+            block_number: block.number,
+            extrinsic_index: message.extrinsic_index,
+            tx: extrinsics.get(message.extrinsic_index as usize).and_then(decode_transact),
+        };
+
+        storage.set(PREFIX, &tx_key(message.index), &tx.encode());
+        indices.push(message.index);
+    }
+
+    storage.set(PREFIX, &block_key(&block.hash), &indices.encode());
+
+    Ok(())
+}
+
+/// Moves the index from `head` to the block `new`, rolling back the retracted blocks.
+fn update<C, S>(
+    client: &C,
+    storage: &mut S,
+    head: &mut Head,
+    new: Hash,
+) -> sp_blockchain::Result<()>
+where
+    C: ProvideRuntimeApi<Block> + BlockBackend<Block>,
+    C: HeaderMetadata<Block, Error = sp_blockchain::Error>,
+    C::Api: ZeropoolApi<Block>,
+    S: OffchainStorage,
+{
+    let route = sp_blockchain::tree_route(client, head.hash, new)?;
+
+    for block in route.retracted() {
+        let indices: Vec<U256> = read(storage, &block_key(&block.hash)).unwrap_or_default();
+        for index in indices {
+            storage.remove(PREFIX, &tx_key(index));
+        }
+        storage.remove(PREFIX, &block_key(&block.hash));
+    }
+
+    for block in route.enacted() {
+        index_block(client, storage, block)?;
+    }
+
+    head.hash = new;
+    head.pool_index = client.runtime_api().pool_index(&BlockId::hash(new))?;
+    storage.set(PREFIX, HEAD_KEY, &head.encode());
+
+    Ok(())
+}
+
+/// Runs the indexer until the node shuts down.
+pub async fn run<C, B>(client: Arc<C>, backend: Arc<B>)
+where
+    C: ProvideRuntimeApi<Block> + BlockBackend<Block> + BlockchainEvents<Block>,
+    C: HeaderBackend<Block> + HeaderMetadata<Block, Error = sp_blockchain::Error>,
+    C: Send + Sync + 'static,
+    C::Api: ZeropoolApi<Block>,
+    B: Backend<Block>,
+{
+    let mut storage = match backend.offchain_storage() {
+        Some(storage) => storage,
+        None => {
+            log::warn!("Offchain storage is not available, the zeropool indexer is disabled");
+            return
+        },
+    };
+
+    let mut notifications =
+        client.import_notification_stream().filter(|n| future::ready(n.is_new_best));
+
+    let mut head = read(&storage, HEAD_KEY)
+        .unwrap_or(Head { hash: client.info().genesis_hash, pool_index: U256::zero() });
+
+    // Catch up with the chain before following it
+    let mut next = Some(client.info().best_hash);
+    while let Some(hash) = next {
+        if let Err(err) = update(&*client, &mut storage, &mut head, hash) {
+            log::warn!("Zeropool indexer failed to process block {}: {:?}", hash, err);
+        }
+
+        next = notifications.next().await.map(|n| n.hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::offchain::storage::InMemOffchainStorage;
+
+    #[test]
+    fn test_transactions() {
+        let mut storage = InMemOffchainStorage::default();
+        assert!(transactions(&storage, U256::zero(), 10).is_empty());
+
+        for tx in 0..3u32 {
+            let index = U256::from(tx * 128);
+            let tx = IndexedTx {
+                index,
+                all_messages_hash: U256::zero(),
+                commitment: U256::from(tx),
+                block_hash: Hash::default(),
+                block_number: tx,
+                extrinsic_index: 1,
+                tx: None,
+            };
+            storage.set(PREFIX, &tx_key(index), &tx.encode());
+        }
+        let head = Head { hash: Hash::default(), pool_index: U256::from(3 * 128) };
+        storage.set(PREFIX, HEAD_KEY, &head.encode());
+
+        let indices = |from_index: u32, limit| {
+            transactions(&storage, U256::from(from_index), limit)
+                .into_iter()
+                .map(|tx| tx.index)
+                .collect::<Vec<_>>()
+        };
+
+        // Same ranges as `zeropool_getMemos`, a range starting inside a transaction includes it
+        assert_eq!(indices(130, 10), vec![U256::from(128), U256::from(256)]);
+        assert_eq!(indices(0, 2), vec![U256::zero(), U256::from(128)]);
+        assert!(indices(3 * 128, 10).is_empty());
+
+        // Missing transactions count against the limit
+        storage.remove(PREFIX, &tx_key(U256::from(128)));
+        let found = transactions(&storage, U256::zero(), 2);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].index, U256::zero());
+    }
+}
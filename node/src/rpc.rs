@@ -8,43 +8,61 @@
 use std::sync::Arc;
 
 use node_template_runtime::{opaque::Block, AccountId, Balance, Index};
+use sc_client_api::{Backend, BlockchainEvents, ProofProvider};
+use sc_rpc::SubscriptionTaskExecutor;
 pub use sc_rpc_api::DenyUnsafe;
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_block_builder::BlockBuilder;
 use sp_blockchain::{Error as BlockChainError, HeaderBackend, HeaderMetadata};
 
+pub mod zeropool;
+
 /// Full client dependencies.
-pub struct FullDeps<C, P> {
+pub struct FullDeps<C, P, B> {
     /// The client instance to use.
     pub client: Arc<C>,
     /// Transaction pool instance.
     pub pool: Arc<P>,
+    /// The backend instance, used to read the offchain database.
+    pub backend: Arc<B>,
     /// Whether to deny unsafe calls
     pub deny_unsafe: DenyUnsafe,
+    /// Executor for the subscription tasks.
+    pub subscription_executor: SubscriptionTaskExecutor,
 }
 
 /// Instantiate all full RPC extensions.
-pub fn create_full<C, P>(deps: FullDeps<C, P>) -> jsonrpc_core::IoHandler<sc_rpc::Metadata>
+pub fn create_full<C, P, B>(deps: FullDeps<C, P, B>) -> jsonrpc_core::IoHandler<sc_rpc::Metadata>
 where
     C: ProvideRuntimeApi<Block>,
     C: HeaderBackend<Block> + HeaderMetadata<Block, Error = BlockChainError> + 'static,
+    C: ProofProvider<Block> + BlockchainEvents<Block>,
     C: Send + Sync + 'static,
     C::Api: substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Index>,
     C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
+    C::Api: pallet_zeropool_runtime_api::ZeropoolApi<Block>,
     C::Api: BlockBuilder<Block>,
     P: TransactionPool + 'static,
+    B: Backend<Block> + Send + Sync + 'static,
 {
     use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApi};
     use substrate_frame_rpc_system::{FullSystem, SystemApi};
+    use zeropool::{Zeropool, ZeropoolApi};
 
     let mut io = jsonrpc_core::IoHandler::default();
-    let FullDeps { client, pool, deny_unsafe } = deps;
+    let FullDeps { client, pool, backend, deny_unsafe, subscription_executor } = deps;
 
     io.extend_with(SystemApi::to_delegate(FullSystem::new(client.clone(), pool, deny_unsafe)));
 
     io.extend_with(TransactionPaymentApi::to_delegate(TransactionPayment::new(client.clone())));
 
+    io.extend_with(ZeropoolApi::to_delegate(Zeropool::new(
+        client.clone(),
+        backend,
+        subscription_executor,
+    )));
+
     // Extend this RPC with a custom API by using the following syntax.
     // `YourRpcStruct` should have a reference to a client, which is needed
     // to call into the runtime.
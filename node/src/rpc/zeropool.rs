@@ -0,0 +1,471 @@
+//! ZeroPool specific RPC methods.
+
+use std::sync::Arc;
+
+use codec::Decode;
+use futures::{future, stream, FutureExt, SinkExt, StreamExt};
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use jsonrpc_pubsub::{manager::SubscriptionManager, typed::Subscriber, SubscriptionId};
+use node_template_runtime::opaque::Block;
+use pallet_zeropool_runtime_api::{memo_key, tx_index, ZeropoolApi as ZeropoolRuntimeApi};
+use sc_client_api::{Backend, BlockchainEvents, ProofProvider};
+use sc_rpc::SubscriptionTaskExecutor;
+use serde::{Deserialize, Serialize};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::{HeaderBackend, HeaderMetadata};
+use sp_core::{
+    offchain::{OffchainStorage, STORAGE_PREFIX},
+    Bytes, U256,
+};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+
+use crate::indexer::{self, IndexedTx};
+
+/// Maximum number of commitments returned by a single call.
+const MAX_COMMITMENTS: u32 = 1024;
+
+/// Maximum number of memos returned by a single call.
+const MAX_MEMOS: u32 = 1024;
+
+/// Maximum number of indexed transactions returned by a single call.
+const MAX_TRANSACTIONS: u32 = 1024;
+
+/// Error code for failures of the runtime API calls.
+const RUNTIME_ERROR: i64 = 1;
+
+/// Error code for failures to read the offchain database.
+const OFFCHAIN_ERROR: i64 = 2;
+
+/// An output commitment of a pool transaction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Commitment {
+    /// Pool index of the first leaf of the commitment.
+    pub index: U256,
+    /// The commitment itself.
+    pub commitment: U256,
+}
+
+/// Commitments along with a storage proof against the state root of the block `at`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitmentsWithProof<Hash> {
+    /// Block the commitments were read at.
+    pub at: Hash,
+    /// Commitments ordered by index.
+    pub commitments: Vec<Commitment>,
+    /// Trie nodes proving the commitments.
+    pub proof: Vec<Bytes>,
+}
+
+/// An encrypted memo of a pool transaction.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Memo {
+    /// Pool index of the first leaf of the commitment.
+    pub index: U256,
+    /// Output commitment of the transaction.
+    pub commitment: U256,
+    /// Encrypted notes and accounts.
+    pub ciphertext: Bytes,
+}
+
+/// A `Message` event of the pool along with its location.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Message<Hash> {
+    /// Pool index of the transaction, the index of the first leaf of the commitment.
+    pub index: U256,
+    /// Hash of all the messages up to and including this one.
+    pub all_messages_hash: U256,
+    /// Output commitment of the transaction.
+    pub commitment: U256,
+    /// Encrypted notes and accounts.
+    pub memo: Bytes,
+    /// Block the message was emitted in.
+    pub block_hash: Hash,
+    /// Index of the extrinsic in the block.
+    pub extrinsic_index: u32,
+}
+
+/// ZeroPool RPC methods.
+#[rpc]
+pub trait ZeropoolApi<BlockHash> {
+    /// RPC metadata
+    type Metadata;
+
+    /// Returns up to `limit` commitments starting from the transaction that the pool index
+    /// `from_index` falls into, along with a storage proof.
+    #[rpc(name = "zeropool_getCommitments")]
+    fn get_commitments(
+        &self,
+        from_index: U256,
+        limit: u32,
+        at: Option<BlockHash>,
+    ) -> Result<CommitmentsWithProof<BlockHash>>;
+
+    /// Returns up to `limit` memos starting from the transaction that the pool index
+    /// `from_index` falls into. The memos are read from the offchain database, so the node has
+    /// to run with offchain indexing enabled.
+    #[rpc(name = "zeropool_getMemos")]
+    fn get_memos(&self, from_index: U256, limit: u32) -> Result<Vec<Memo>>;
+
+    /// Returns up to `limit` transactions of the best chain starting from the transaction that
+    /// the pool index `from_index` falls into. Requires the node to run with
+    /// `--zeropool-indexer`.
+    #[rpc(name = "zeropool_getTransactions")]
+    fn get_transactions(&self, from_index: U256, limit: u32) -> Result<Vec<IndexedTx>>;
+
+    /// Streams the messages of the new best blocks, or of the finalized blocks if `finalized` is
+    /// set. When `from_index` is given, the messages already on chain are sent first, starting
+    /// from the transaction that `from_index` falls into. A client resumes after reconnecting by
+    /// passing the pool index after the last message it received.
+    ///
+    /// Messages of the best blocks can be retracted by a reorg, clients should track the block
+    /// hashes or subscribe to the finalized blocks.
+    #[pubsub(subscription = "zeropool_messages", subscribe, name = "zeropool_subscribeMessages")]
+    fn subscribe_messages(
+        &self,
+        metadata: Self::Metadata,
+        subscriber: Subscriber<Message<BlockHash>>,
+        from_index: Option<U256>,
+        finalized: Option<bool>,
+    );
+
+    /// Cancels a messages subscription.
+    #[pubsub(
+        subscription = "zeropool_messages",
+        unsubscribe,
+        name = "zeropool_unsubscribeMessages"
+    )]
+    fn unsubscribe_messages(
+        &self,
+        metadata: Option<Self::Metadata>,
+        id: SubscriptionId,
+    ) -> Result<bool>;
+}
+
+/// Implementation of the ZeroPool RPC methods.
+pub struct Zeropool<C, B> {
+    client: Arc<C>,
+    backend: Arc<B>,
+    manager: SubscriptionManager,
+}
+
+impl<C, B> Zeropool<C, B> {
+    /// Creates a new instance of the ZeroPool RPC handler.
+    pub fn new(client: Arc<C>, backend: Arc<B>, executor: SubscriptionTaskExecutor) -> Self {
+        Self { client, backend, manager: SubscriptionManager::new(Arc::new(executor)) }
+    }
+}
+
+fn runtime_error(message: &str, err: impl std::fmt::Debug) -> RpcError {
+    RpcError {
+        code: ErrorCode::ServerError(RUNTIME_ERROR),
+        message: message.into(),
+        data: Some(format!("{:?}", err).into()),
+    }
+}
+
+fn offchain_error(message: &str) -> RpcError {
+    RpcError { code: ErrorCode::ServerError(OFFCHAIN_ERROR), message: message.into(), data: None }
+}
+
+/// Whether the message of the transaction at `index` is sent to a subscription resuming from the
+/// pool index `from_index`.
+fn is_resumed(from_index: Option<U256>, index: U256) -> bool {
+    from_index.map_or(true, |from_index| index >= tx_index(from_index))
+}
+
+/// Returns the block after which the messages of the transactions from the pool index
+/// `from_index` on start, or `head` if there are none.
+fn resume_point<C>(
+    client: &C,
+    from_index: U256,
+    head: <Block as BlockT>::Hash,
+) -> Result<<Block as BlockT>::Hash>
+where
+    C: ProvideRuntimeApi<Block> + HeaderBackend<Block>,
+    C::Api: ZeropoolRuntimeApi<Block>,
+{
+    let from_index = tx_index(from_index);
+    let block_hash = |number| {
+        client
+            .hash(number)
+            .ok()
+            .flatten()
+            .ok_or_else(|| runtime_error("Block not found.", number))
+    };
+    let pool_index_at = |hash| {
+        client
+            .runtime_api()
+            .pool_index(&BlockId::hash(hash))
+            .map_err(|err| runtime_error("Unable to query the pool index.", err))
+    };
+
+    if pool_index_at(head)? <= from_index {
+        return Ok(head)
+    }
+
+    // The pool index only grows, find the first block that went past `from_index`
+    let mut low = 0;
+    let mut high = client
+        .number(head)
+        .ok()
+        .flatten()
+        .ok_or_else(|| runtime_error("Block not found.", head))?;
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if pool_index_at(block_hash(mid)?)? > from_index {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+
+    block_hash(low.saturating_sub(1))
+}
+
+/// Reads up to `limit` memos from the transaction that `from_index` falls into up to the pool
+/// index `end`. Only the next `limit` transactions are looked up, missing memos count against
+/// the limit.
+fn memos<S: OffchainStorage>(
+    storage: &S,
+    from_index: U256,
+    end: U256,
+    limit: usize,
+) -> Result<Vec<Memo>> {
+    let step = U256::from(128u32);
+    let mut index = tx_index(from_index);
+    let end = end.min(index.saturating_add(step * limit));
+    let mut memos = Vec::new();
+
+    while index < end && memos.len() < limit {
+        if let Some(raw) = storage.get(STORAGE_PREFIX, &memo_key(index)) {
+            let (index, commitment, ciphertext) = <(U256, U256, Vec<u8>)>::decode(&mut &raw[..])
+                .map_err(|_| offchain_error("Unable to decode a memo."))?;
+            memos.push(Memo { index, commitment, ciphertext: ciphertext.into() });
+        }
+        index += step;
+    }
+
+    Ok(memos)
+}
+
+/// Collects the messages of the blocks enacted on the way from `from` to `to`.
+fn enacted_messages<C>(
+    client: &C,
+    from: <Block as BlockT>::Hash,
+    to: <Block as BlockT>::Hash,
+) -> sp_blockchain::Result<Vec<Message<<Block as BlockT>::Hash>>>
+where
+    C: ProvideRuntimeApi<Block> + HeaderMetadata<Block, Error = sp_blockchain::Error>,
+    C::Api: ZeropoolRuntimeApi<Block>,
+{
+    let route = sp_blockchain::tree_route(client, from, to)?;
+    let mut messages = Vec::new();
+
+    for block in route.enacted() {
+        let events = client.runtime_api().messages(&BlockId::hash(block.hash))?;
+        messages.extend(events.into_iter().map(|event| Message {
+            index: event.index,
+            all_messages_hash: event.all_messages_hash,
+            commitment: event.commitment,
+            memo: event.memo.into(),
+            block_hash: block.hash,
+            extrinsic_index: event.extrinsic_index,
+        }));
+    }
+
+    Ok(messages)
+}
+
+impl<C, B> ZeropoolApi<<Block as BlockT>::Hash> for Zeropool<C, B>
+where
+    C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + ProofProvider<Block>,
+    C: HeaderMetadata<Block, Error = sp_blockchain::Error> + BlockchainEvents<Block>,
+    C: Send + Sync + 'static,
+    C::Api: ZeropoolRuntimeApi<Block>,
+    B: Backend<Block> + Send + Sync + 'static,
+{
+    type Metadata = sc_rpc::Metadata;
+
+    fn get_commitments(
+        &self,
+        from_index: U256,
+        limit: u32,
+        at: Option<<Block as BlockT>::Hash>,
+    ) -> Result<CommitmentsWithProof<<Block as BlockT>::Hash>> {
+        let at = at.unwrap_or_else(|| self.client.info().best_hash);
+        let block = BlockId::hash(at);
+
+        let entries = self
+            .client
+            .runtime_api()
+            .commitments(&block, from_index, limit.min(MAX_COMMITMENTS))
+            .map_err(|err| runtime_error("Unable to query commitments.", err))?;
+
+        let proof = self
+            .client
+            .read_proof(&block, &mut entries.iter().map(|(_, _, key)| key.as_slice()))
+            .map_err(|err| runtime_error("Unable to create a storage proof.", err))?;
+
+        Ok(CommitmentsWithProof {
+            at,
+            commitments: entries
+                .into_iter()
+                .map(|(index, commitment, _)| Commitment { index, commitment })
+                .collect(),
+            proof: proof.iter_nodes().map(Bytes).collect(),
+        })
+    }
+
+    fn get_memos(&self, from_index: U256, limit: u32) -> Result<Vec<Memo>> {
+        let storage = self.backend.offchain_storage().ok_or_else(|| {
+            offchain_error("Offchain storage is not available, enable offchain indexing.")
+        })?;
+
+        let best = BlockId::hash(self.client.info().best_hash);
+        let end = self
+            .client
+            .runtime_api()
+            .pool_index(&best)
+            .map_err(|err| runtime_error("Unable to query the pool index.", err))?;
+
+        memos(&storage, from_index, end, limit.min(MAX_MEMOS) as usize)
+    }
+
+    fn get_transactions(&self, from_index: U256, limit: u32) -> Result<Vec<IndexedTx>> {
+        let storage = self
+            .backend
+            .offchain_storage()
+            .ok_or_else(|| offchain_error("Offchain storage is not available."))?;
+
+        Ok(indexer::transactions(&storage, from_index, limit.min(MAX_TRANSACTIONS) as usize))
+    }
+
+    fn subscribe_messages(
+        &self,
+        _metadata: Self::Metadata,
+        subscriber: Subscriber<Message<<Block as BlockT>::Hash>>,
+        from_index: Option<U256>,
+        finalized: Option<bool>,
+    ) {
+        let client = self.client.clone();
+        let finalized = finalized.unwrap_or(false);
+
+        // Subscribe before reading the head so that no block is missed
+        let (head, notifications) = if finalized {
+            let notifications = client.finality_notification_stream().map(|n| n.hash).boxed();
+            (client.info().finalized_hash, notifications)
+        } else {
+            let notifications = client
+                .import_notification_stream()
+                .filter(|n| future::ready(n.is_new_best))
+                .map(|n| n.hash)
+                .boxed();
+            (client.info().best_hash, notifications)
+        };
+
+        let start = match from_index {
+            Some(from_index) => match resume_point(&*client, from_index, head) {
+                Ok(start) => start,
+                Err(err) => {
+                    let _ = subscriber.reject(err);
+                    return
+                },
+            },
+            None => head,
+        };
+
+        self.manager.add(subscriber, move |sink| {
+            let mut last = start;
+            let messages =
+                stream::once(future::ready(head)).chain(notifications).flat_map(move |hash| {
+                    let messages = match enacted_messages(&*client, last, hash) {
+                        Ok(messages) => {
+                            last = hash;
+                            messages
+                        },
+                        Err(err) => {
+                            log::warn!("Unable to read the pool messages of {}: {:?}", hash, err);
+                            Vec::new()
+                        },
+                    };
+
+                    stream::iter(
+                        messages
+                            .into_iter()
+                            .filter(move |message| is_resumed(from_index, message.index)),
+                    )
+                });
+
+            messages
+                .map(|message| Ok::<_, ()>(Ok(message)))
+                .forward(
+                    sink.sink_map_err(|err| {
+                        log::warn!("Error sending the pool messages: {:?}", err)
+                    }),
+                )
+                .map(drop)
+        });
+    }
+
+    fn unsubscribe_messages(
+        &self,
+        _metadata: Option<Self::Metadata>,
+        id: SubscriptionId,
+    ) -> Result<bool> {
+        Ok(self.manager.cancel(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+    use sp_core::offchain::storage::InMemOffchainStorage;
+
+    #[test]
+    fn test_memos() {
+        let mut storage = InMemOffchainStorage::default();
+        for tx in 0..3u32 {
+            let index = U256::from(tx * 128);
+            let memo = (index, U256::from(tx), vec![tx as u8]);
+            storage.set(STORAGE_PREFIX, &memo_key(index), &memo.encode());
+        }
+        let end = U256::from(3 * 128);
+        let indices = |from_index: u32, limit| {
+            memos(&storage, U256::from(from_index), end, limit)
+                .unwrap()
+                .into_iter()
+                .map(|memo| memo.index)
+                .collect::<Vec<_>>()
+        };
+
+        // Memos are keyed by the index of their first leaf, a range starting inside a
+        // transaction includes it
+        assert_eq!(indices(130, 10), vec![U256::from(128), U256::from(256)]);
+        assert_eq!(indices(128, 1), vec![U256::from(128)]);
+        assert!(indices(3 * 128, 10).is_empty());
+
+        // Missing memos count against the limit
+        storage.remove(STORAGE_PREFIX, &memo_key(U256::from(128)));
+        let found = memos(&storage, U256::zero(), end, 2).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].index, U256::zero());
+    }
+    #[test]
+    fn test_is_resumed() {
+        assert!(is_resumed(None, U256::zero()));
+
+        // Resuming from the pool index after the last message
+        assert!(!is_resumed(Some(U256::from(256)), U256::from(128)));
+        assert!(is_resumed(Some(U256::from(256)), U256::from(256)));
+
+        // An index inside a transaction resumes from that transaction
+        assert!(is_resumed(Some(U256::from(130)), U256::from(128)));
+        assert!(!is_resumed(Some(U256::from(130)), U256::zero()));
+    }
+}
@@ -112,8 +112,8 @@ pub fn new_partial(
 
     let slot_duration = sc_consensus_aura::slot_duration(&*client)?;
 
-    let import_queue =
-        sc_consensus_aura::import_queue::<AuraPair, _, _, _, _, _, _>(ImportQueueParams {
+    let import_queue = sc_consensus_aura::import_queue::<AuraPair, _, _, _, _, _, _>(
+        ImportQueueParams {
             block_import: grandpa_block_import.clone(),
             justification_import: Some(Box::new(grandpa_block_import.clone())),
             client: client.clone(),
@@ -135,7 +135,8 @@ pub fn new_partial(
             registry: config.prometheus_registry(),
             check_for_equivocation: Default::default(),
             telemetry: telemetry.as_ref().map(|x| x.handle()),
-        })?;
+        },
+    )?;
 
     Ok(sc_service::PartialComponents {
         client,
@@ -156,8 +157,12 @@ fn remote_keystore(_url: &String) -> Result<Arc<LocalKeystore>, &'static str> {
     Err("Remote Keystore not supported.")
 }
 
-/// Builds a new service for a full client.
-pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError> {
+/// Builds a new service for a full client. `zeropool_indexer` enables the indexer of the pool
+/// transactions.
+pub fn new_full(
+    mut config: Configuration,
+    zeropool_indexer: bool,
+) -> Result<TaskManager, ServiceError> {
     let sc_service::PartialComponents {
         client,
         backend,
@@ -221,13 +226,27 @@ pub fn new_full(mut config: Configuration) -> Result<TaskManager, ServiceError>
     let enable_grandpa = !config.disable_grandpa;
     let prometheus_registry = config.prometheus_registry().cloned();
 
+    if zeropool_indexer {
+        task_manager.spawn_handle().spawn(
+            "zeropool-indexer",
+            None,
+            crate::indexer::run(client.clone(), backend.clone()),
+        );
+    }
+
     let rpc_extensions_builder = {
         let client = client.clone();
         let pool = transaction_pool.clone();
+        let backend = backend.clone();
 
-        Box::new(move |deny_unsafe, _| {
-            let deps =
-                crate::rpc::FullDeps { client: client.clone(), pool: pool.clone(), deny_unsafe };
+        Box::new(move |deny_unsafe, subscription_executor| {
+            let deps = crate::rpc::FullDeps {
+                client: client.clone(),
+                pool: pool.clone(),
+                backend: backend.clone(),
+                deny_unsafe,
+                subscription_executor,
+            };
 
             Ok(crate::rpc::create_full(deps))
         })
@@ -0,0 +1,95 @@
+//! Runtime API definition for the ZeroPool pallet.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::{Decode, Encode, MaxEncodedLen};
+use scale_info::TypeInfo;
+use sp_core::{RuntimeDebug, U256};
+use sp_runtime::{traits::AtLeast32BitUnsigned, Permill};
+use sp_std::vec::Vec;
+
+/// A `Message` event of the pool.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct Message {
+    /// Index of the extrinsic that emitted the event.
+    pub extrinsic_index: u32,
+    /// Pool index of the transaction, see `tx_index`.
+    pub index: U256,
+    pub all_messages_hash: U256,
+    pub commitment: U256,
+    pub memo: Vec<u8>,
+}
+
+/// Running totals of the pool, in pool token units.
+#[derive(Clone, PartialEq, Eq, Default, Encode, Decode, RuntimeDebug)]
+pub struct Ledger {
+    pub deposited: U256,
+    pub withdrawn: U256,
+    pub fees: U256,
+    /// Deposits minus withdrawals and fees.
+    pub shielded_supply: U256,
+}
+
+/// Protocol fee paid to the treasury on top of the operator fee.
+#[derive(
+    Clone, Copy, Default, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug,
+)]
+pub struct ProtocolFee {
+    /// Share of the operator fee that goes to the treasury instead.
+    pub operator_fee_share: Permill,
+    /// Charge on deposit and withdrawal amounts, in basis points. Paid by the depositor on top
+    /// of the deposit, direct deposits included, and deducted from the withdrawal.
+    pub amount_bps: u16,
+}
+
+impl ProtocolFee {
+    pub const MAX_BPS: u16 = 10_000;
+
+    /// Returns the charge on a deposit or withdrawal of `amount`.
+    pub fn amount_fee<Balance: AtLeast32BitUnsigned + Copy>(&self, amount: Balance) -> Balance {
+        Permill::from_parts(u32::from(self.amount_bps) * 100).mul_floor(amount)
+    }
+}
+
+/// Returns the pool index of the transaction that `index` falls into.
+///
+/// A transaction takes 128 leaves of the tree, its pool index is the index of the first one. The
+/// ranges of the runtime API and of the RPC methods start at `tx_index(from_index)`, so they
+/// include the transaction that `from_index` falls into.
+pub fn tx_index(index: U256) -> U256 {
+    index - index % U256::from(128u32)
+}
+
+/// Prefix of the memo keys in the offchain database.
+pub const MEMO_KEY_PREFIX: &[u8] = b"zeropool::memo::";
+
+/// Offchain database key of the memo of the commitment at `index`. Keys of consecutive
+/// commitments are ordered.
+pub fn memo_key(index: U256) -> Vec<u8> {
+    let mut key = MEMO_KEY_PREFIX.to_vec();
+    let mut index_bytes = [0u8; 32];
+    index.to_big_endian(&mut index_bytes);
+    key.extend_from_slice(&index_bytes);
+    key
+}
+
+sp_api::decl_runtime_apis! {
+    pub trait ZeropoolApi {
+        /// Returns the index of the next commitment.
+        fn pool_index() -> U256;
+
+        /// Returns the `Message` events emitted in the block.
+        fn messages() -> Vec<Message>;
+
+        /// Returns up to `limit` commitments starting from the transaction at the pool index
+        /// `from_index` as `(index, commitment, storage_key)`, see `tx_index`. The storage keys
+        /// can be used to request a read proof of the commitments.
+        fn commitments(from_index: U256, limit: u32) -> Vec<(U256, U256, Vec<u8>)>;
+
+        /// Returns the totals deposited, withdrawn and paid as fees.
+        fn ledger() -> Ledger;
+
+        /// Returns the protocol fee charged on top of the operator fee.
+        fn protocol_fee() -> ProtocolFee;
+    }
+}
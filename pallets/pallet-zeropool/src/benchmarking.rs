@@ -1,44 +1,108 @@
-//! Benchmarking setup for pallet-template
+//! Benchmarking setup for pallet-zeropool
 
 use super::*;
 
 #[allow(unused_imports)]
 use crate::Pallet as Zeropool;
-use frame_benchmarking::{benchmarks, whitelisted_caller};
+use crate::{
+    fixtures::{
+        ACCOUNT, BATCH_PROOF, BATCH_TREE_PROOF, BATCH_TREE_VK, DEPOSIT, DIRECT_DEPOSIT_VK,
+        TRANSFER, WITHDRAW,
+    },
+    tx_decoder::MAX_MEMO_SIZE,
+};
+use frame_benchmarking::benchmarks;
 use frame_system::RawOrigin;
-use hex_literal::hex;
-use sp_core::crypto::AccountId32;
-
-const OWNER: AccountId32 =
-    AccountId32::new(hex!("d000ac5048ae858aca2e6aa43e00661562a47026fe88ff83992430204a159752"));
+use maybestd::vec;
+use sp_runtime::traits::Bounded;
 
 const TRANSFER_VK: &[u8] = include_bytes!("../../../js/params/transfer_verification_key.bin");
 const TREE_VK: &[u8] = include_bytes!("../../../js/params/tree_verification_key.bin");
 
-// fn owner() -> Origin {
-//     Origin::signed(OWNER)
-// }
-
-// fn init_state() {
-//     let transfer_vk = std::fs::read("../../js/params/transfer_verification_key.bin")
-//         .expect("download and place the params directory into the js directory");
-//     assert_ok!(Zeropool::set_transfer_vk(owner(), transfer_vk));
-//
-//     let tree_vk = std::fs::read("../../js/params/tree_verification_key.bin")
-//         .expect("download and place the params directory into the js directory");
-//     assert_ok!(Zeropool::set_tree_vk(owner(), tree_vk));
-//
-//     assert_ok!(ZeropoolOperatorManager::set_operator(owner(), OWNER));
-// }
+/// Sets the verification keys and funds the account of the fixtures, which also includes them.
+fn init_state<T: Config>() -> T::AccountId {
+    let owner = <Owner<T>>::get();
+    Zeropool::<T>::set_transfer_vk(RawOrigin::Signed(owner.clone()).into(), TRANSFER_VK.to_vec())
+        .unwrap();
+    Zeropool::<T>::set_tree_vk(RawOrigin::Signed(owner).into(), TREE_VK.to_vec()).unwrap();
+
+    let account = T::AccountId::decode(&mut &ACCOUNT[..]).unwrap();
+    T::Currency::make_free_balance_be(&account, BalanceOf::<T>::max_value() / 4u32.into());
+    T::Currency::make_free_balance_be(&Zeropool::<T>::account_id(), T::Currency::minimum_balance());
+
+    account
+}
 
 benchmarks! {
     set_transfer_vk {
-        let origin: T::AccountId = whitelisted_caller();
+        let origin = <Owner<T>>::get();
     }: _(RawOrigin::Signed(origin), TRANSFER_VK.to_vec())
 
     set_tree_vk {
-        let origin: T::AccountId = whitelisted_caller();
+        let origin = <Owner<T>>::get();
     }: _(RawOrigin::Signed(origin), TREE_VK.to_vec())
 
+    transact_deposit {
+        let account = init_state::<T>();
+    }: {
+        Zeropool::<T>::process_tx(account, DEPOSIT)?;
+    }
+
+    transact_transfer {
+        let account = init_state::<T>();
+        Zeropool::<T>::process_tx(account.clone(), DEPOSIT)?;
+    }: {
+        Zeropool::<T>::process_tx(account, TRANSFER)?;
+    }
+
+    transact_withdraw {
+        let account = init_state::<T>();
+        Zeropool::<T>::process_tx(account.clone(), DEPOSIT)?;
+        Zeropool::<T>::process_tx(account.clone(), TRANSFER)?;
+    }: {
+        Zeropool::<T>::process_tx(account, WITHDRAW)?;
+    }
+
+    // The part of a transaction that grows with its memo: hashing, the event and the offchain copy.
+    memo {
+        let m in 0 .. MAX_MEMO_SIZE as u32;
+        let memo = vec![0u8; m as usize];
+    }: {
+        Zeropool::<T>::append_commitment(U256::ZERO, U256::ZERO, &keccak_256(&memo), &memo);
+    }
+
+    // Both proofs of a batch of the two direct deposits of the fixtures.
+    process_direct_deposits {
+        let owner = <Owner<T>>::get();
+        Zeropool::<T>::set_direct_deposit_vk(
+            RawOrigin::Signed(owner.clone()).into(),
+            DIRECT_DEPOSIT_VK.to_vec(),
+        )?;
+        Zeropool::<T>::set_tree_vk(
+            RawOrigin::Signed(owner.clone()).into(),
+            BATCH_TREE_VK.to_vec(),
+        )?;
+
+        let deposits = [(1, 2, 3000u32), (3, 4, 2000)];
+        for (id, (diversifier, pk, amount)) in deposits.into_iter().enumerate() {
+            let deposit = DirectDeposit {
+                sender: owner.clone(),
+                receiver: ZkAddress { diversifier: [diversifier; 10], pk: [pk; 32] },
+                amount: amount.into(),
+                created_at: <frame_system::Pallet<T>>::block_number(),
+            };
+            <DirectDeposits<T>>::insert(id as u64, deposit);
+        }
+        <NextDirectDepositId<T>>::put(2);
+    }: {
+        Zeropool::<T>::process_direct_deposit_batch(
+            2,
+            NativeU256::from(7),
+            NativeU256::from(8),
+            BATCH_PROOF,
+            BATCH_TREE_PROOF,
+        )?;
+    }
+
     impl_benchmark_test_suite!(Zeropool, crate::mock::new_test_ext(), crate::mock::Test);
 }
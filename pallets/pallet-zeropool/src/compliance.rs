@@ -0,0 +1,135 @@
+//! Screening of the accounts that move funds in and out of the pool.
+//!
+//! The pool asks its `ComplianceFilter` before taking a deposit and before paying a withdrawal.
+//! The `()` filter lets everyone through, the pallet below keeps an allow-list and a deny-list
+//! managed by `AdminOrigin`.
+
+pub trait ComplianceFilter<AccountId, Balance> {
+    /// Whether `account` may deposit `amount` into the pool.
+    fn can_deposit(account: &AccountId, amount: Balance) -> bool;
+    /// Whether `account` may receive a withdrawal of `amount` from the pool.
+    fn can_withdraw(account: &AccountId, amount: Balance) -> bool;
+}
+
+impl<AccountId, Balance> ComplianceFilter<AccountId, Balance> for () {
+    fn can_deposit(_account: &AccountId, _amount: Balance) -> bool {
+        true
+    }
+
+    fn can_withdraw(_account: &AccountId, _amount: Balance) -> bool {
+        true
+    }
+}
+
+pub use pallet::*;
+
+/// Screens the counterparties of the pool with an allow-list and a deny-list.
+#[frame_support::pallet]
+pub mod pallet {
+    use super::ComplianceFilter;
+    use frame_support::pallet_prelude::*;
+    use frame_system::pallet_prelude::*;
+
+    #[pallet::config]
+    pub trait Config: frame_system::Config {
+        type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
+
+        /// Origin that manages the lists.
+        type AdminOrigin: EnsureOrigin<Self::Origin>;
+    }
+
+    #[pallet::pallet]
+    #[pallet::generate_store(pub(super) trait Store)]
+    pub struct Pallet<T>(_);
+
+    /// Whether only the accounts in `AllowList` can deposit and withdraw.
+    #[pallet::storage]
+    pub type AllowListEnabled<T: Config> = StorageValue<_, bool, ValueQuery>;
+
+    #[pallet::storage]
+    pub type AllowList<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, ()>;
+
+    /// Accounts that can neither deposit nor withdraw, whether the allow-list is enabled or not.
+    #[pallet::storage]
+    pub type DenyList<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, ()>;
+
+    #[pallet::event]
+    #[pallet::generate_deposit(pub(super) fn deposit_event)]
+    pub enum Event<T: Config> {
+        /// [enabled]
+        AllowListEnabledSet(bool),
+        /// [who, allowed]
+        AllowListUpdated(T::AccountId, bool),
+        /// [who, denied]
+        DenyListUpdated(T::AccountId, bool),
+    }
+
+    impl<T: Config> Pallet<T> {
+        /// Whether `account` passes both lists.
+        pub fn is_allowed(account: &T::AccountId) -> bool {
+            if <DenyList<T>>::contains_key(account) {
+                return false
+            }
+
+            !<AllowListEnabled<T>>::get() || <AllowList<T>>::contains_key(account)
+        }
+    }
+
+    #[pallet::call]
+    impl<T: Config> Pallet<T> {
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn set_allow_list_enabled(origin: OriginFor<T>, enabled: bool) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            <AllowListEnabled<T>>::put(enabled);
+
+            Self::deposit_event(Event::AllowListEnabledSet(enabled));
+
+            Ok(())
+        }
+
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn set_allowed(
+            origin: OriginFor<T>,
+            who: T::AccountId,
+            allowed: bool,
+        ) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            if allowed {
+                <AllowList<T>>::insert(&who, ());
+            } else {
+                <AllowList<T>>::remove(&who);
+            }
+
+            Self::deposit_event(Event::AllowListUpdated(who, allowed));
+
+            Ok(())
+        }
+
+        #[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+        pub fn set_denied(origin: OriginFor<T>, who: T::AccountId, denied: bool) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            if denied {
+                <DenyList<T>>::insert(&who, ());
+            } else {
+                <DenyList<T>>::remove(&who);
+            }
+
+            Self::deposit_event(Event::DenyListUpdated(who, denied));
+
+            Ok(())
+        }
+    }
+
+    impl<T: Config, Balance> ComplianceFilter<T::AccountId, Balance> for Pallet<T> {
+        fn can_deposit(account: &T::AccountId, _amount: Balance) -> bool {
+            Self::is_allowed(account)
+        }
+
+        fn can_withdraw(account: &T::AccountId, _amount: Balance) -> bool {
+            Self::is_allowed(account)
+        }
+    }
+}
@@ -5,24 +5,41 @@
 extern crate alloc;
 
 use borsh::BorshDeserialize;
+use codec::{Decode, Encode, MaxEncodedLen};
+use core::marker::PhantomData;
 use ff_uint::Uint;
-use frame_support::traits::Currency;
+use frame_support::{
+    traits::{Currency, EnsureOrigin, Get, StorageVersion},
+    BoundedVec, CloneNoBound, EqNoBound, PartialEqNoBound, RuntimeDebug, RuntimeDebugNoBound,
+};
 use maybestd::vec::Vec;
 pub use pallet::*;
+pub use pallet_zeropool_runtime_api::ProtocolFee;
+use pallet_zeropool_runtime_api::{memo_key, tx_index};
+use scale_info::TypeInfo;
 use sp_io::hashing::keccak_256;
 use sp_runtime::traits::Hash;
-use verifier::VK;
+use verifier::{Groth16VK, VK};
 
-use crate::num::{NativeU256, U256};
-pub use crate::operator::OperatorManager;
+pub use crate::{compliance::ComplianceFilter, operator::OperatorManager, weights::WeightInfo};
+use crate::{
+    num::{NativeU256, U256},
+    tx_decoder::TxType,
+};
 
 mod alt_bn128;
+pub mod compliance;
 mod error;
 mod maybestd;
+mod migrations;
 pub mod num;
 pub mod operator;
-mod tx_decoder;
+pub mod tx_decoder;
 mod verifier;
+pub mod weights;
+
+#[cfg(any(test, feature = "runtime-benchmarks"))]
+mod fixtures;
 
 #[cfg(test)]
 mod mock;
@@ -35,6 +52,17 @@ mod benchmarking;
 
 type AccountIdOf<T> = <T as frame_system::Config>::AccountId;
 type BalanceOf<T> = <<T as Config>::Currency as Currency<AccountIdOf<T>>>::Balance;
+type NegativeImbalanceOf<T> =
+    <<T as Config>::Currency as Currency<AccountIdOf<T>>>::NegativeImbalance;
+type RewardBalanceOf<T> = <<T as Config>::RewardCurrency as Currency<AccountIdOf<T>>>::Balance;
+type BlockNumberOf<T> = <T as frame_system::Config>::BlockNumber;
+type PoolLimitsOf<T> = PoolLimits<BalanceOf<T>, BlockNumberOf<T>>;
+type WindowUsageOf<T> = WindowUsage<BalanceOf<T>, BlockNumberOf<T>>;
+type WithdrawalDelayOf<T> = WithdrawalDelay<BalanceOf<T>, BlockNumberOf<T>>;
+type PendingWithdrawalOf<T> = PendingWithdrawal<AccountIdOf<T>, BalanceOf<T>, BlockNumberOf<T>>;
+type DirectDepositOf<T> = DirectDeposit<AccountIdOf<T>, BalanceOf<T>, BlockNumberOf<T>>;
+type ForcedTxOf<T> =
+    ForcedTx<AccountIdOf<T>, BalanceOf<T>, BlockNumberOf<T>, <T as Config>::MaxForcedTxSize>;
 
 pub const FIRST_ROOT: U256 = U256::from_const_str(
     b"11469701942666298368112882412133877458305516134926649826543144744382391691533",
@@ -44,30 +72,189 @@ const R: U256 = U256::from_const_str(
     b"21888242871839275222246405745257275088548364400416034343698204186575808495617",
 );
 
+/// Bumped together with a new step in `migrations` whenever the storage layout changes.
+pub(crate) const STORAGE_VERSION: StorageVersion = StorageVersion::new(2);
+
 #[derive(Debug, BorshDeserialize)]
 pub struct MerkleProof<const L: usize> {
     pub sibling: [U256; L],
     pub path: [bool; L],
 }
 
+/// A set of transaction kinds. Used to pause parts of the pool independently.
+#[derive(
+    Clone, Copy, Default, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug,
+)]
+pub struct TxKinds {
+    pub deposit: bool,
+    pub transfer: bool,
+    pub withdraw: bool,
+}
+
+impl TxKinds {
+    pub const ALL: TxKinds = TxKinds { deposit: true, transfer: true, withdraw: true };
+
+    fn contains(&self, tx_type: &TxType) -> bool {
+        match tx_type {
+            TxType::Deposit => self.deposit,
+            TxType::Transfer => self.transfer,
+            TxType::Withdraw => self.withdraw,
+        }
+    }
+
+    fn union(self, other: TxKinds) -> TxKinds {
+        TxKinds {
+            deposit: self.deposit || other.deposit,
+            transfer: self.transfer || other.transfer,
+            withdraw: self.withdraw || other.withdraw,
+        }
+    }
+
+    fn difference(self, other: TxKinds) -> TxKinds {
+        TxKinds {
+            deposit: self.deposit && !other.deposit,
+            transfer: self.transfer && !other.transfer,
+            withdraw: self.withdraw && !other.withdraw,
+        }
+    }
+}
+
+/// Limits on the pool inflow and outflow. `None` means unlimited.
+#[derive(Clone, Default, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug)]
+pub struct PoolLimits<Balance, BlockNumber> {
+    /// Maximum balance of the pool account.
+    pub tvl_cap: Option<Balance>,
+    /// Maximum amount of a single deposit or withdrawal.
+    pub max_tx_amount: Option<Balance>,
+    /// Length of the withdrawal window in blocks.
+    pub withdraw_window: BlockNumber,
+    /// Maximum total amount withdrawn from the pool during a single window.
+    pub window_limit: Option<Balance>,
+    /// Maximum amount withdrawn to a single account during a single window.
+    pub account_window_limit: Option<Balance>,
+}
+
+/// Amount withdrawn during the window that started at `start`.
+#[derive(Clone, Default, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug)]
+pub struct WindowUsage<Balance, BlockNumber> {
+    pub start: BlockNumber,
+    pub amount: Balance,
+}
+
+/// Withdrawals above `threshold` are queued for `delay` blocks instead of being paid out
+/// immediately.
+#[derive(Clone, Default, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug)]
+pub struct WithdrawalDelay<Balance, BlockNumber> {
+    pub threshold: Option<Balance>,
+    pub delay: BlockNumber,
+}
+
+/// Minimum fee of every transaction, whichever operator submits it. Operators can require more
+/// with their own fee schedule.
+pub type FeePolicy = operator::FeeSchedule;
+
+#[derive(Clone, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug)]
+pub struct PendingWithdrawal<AccountId, Balance, BlockNumber> {
+    pub dest: AccountId,
+    pub amount: Balance,
+    pub unlock_at: BlockNumber,
+    pub frozen: bool,
+}
+
+/// A transaction queued by a user that the operators have to include.
+#[derive(
+    CloneNoBound,
+    PartialEqNoBound,
+    EqNoBound,
+    Encode,
+    Decode,
+    MaxEncodedLen,
+    TypeInfo,
+    RuntimeDebugNoBound,
+)]
+#[codec(mel_bound(AccountId: MaxEncodedLen, Balance: MaxEncodedLen, BlockNumber: MaxEncodedLen))]
+#[scale_info(skip_type_params(MaxSize))]
+pub struct ForcedTx<AccountId, Balance, BlockNumber, MaxSize>
+where
+    AccountId: Clone + PartialEq + Eq + core::fmt::Debug,
+    Balance: Clone + PartialEq + Eq + core::fmt::Debug,
+    BlockNumber: Clone + PartialEq + Eq + core::fmt::Debug,
+    MaxSize: Get<u32>,
+{
+    pub who: AccountId,
+    /// Reserved from `who`, paid to whoever includes the transaction.
+    pub fee: Balance,
+    pub queued_at: BlockNumber,
+    pub tx: BoundedVec<u8, MaxSize>,
+}
+
+/// Shielded address of the receiver of a note.
+#[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug)]
+pub struct ZkAddress {
+    pub diversifier: [u8; 10],
+    pub pk: [u8; 32],
+}
+
+/// Deposit waiting for an operator to insert its note into the pool.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug)]
+pub struct DirectDeposit<AccountId, Balance, BlockNumber> {
+    pub sender: AccountId,
+    pub receiver: ZkAddress,
+    /// Locked in the pool account, a multiple of `DENOMINATOR`.
+    pub amount: Balance,
+    pub created_at: BlockNumber,
+}
+
+/// Size of a direct deposit in the memo of a batch: diversifier, public key and amount.
+const DIRECT_DEPOSIT_SIZE: usize = 10 + 32 + 8;
+
+/// Ensures that the origin is signed by the current owner of the pool.
+pub struct EnsureOwner<T>(PhantomData<T>);
+
+impl<T: Config> EnsureOrigin<T::Origin> for EnsureOwner<T> {
+    type Success = T::AccountId;
+
+    fn try_origin(o: T::Origin) -> Result<Self::Success, T::Origin> {
+        o.into().and_then(|o| match o {
+            frame_system::RawOrigin::Signed(who) if who == <Owner<T>>::get() => Ok(who),
+            r => Err(T::Origin::from(r)),
+        })
+    }
+
+    #[cfg(feature = "runtime-benchmarks")]
+    fn successful_origin() -> T::Origin {
+        T::Origin::from(frame_system::RawOrigin::Signed(<Owner<T>>::get()))
+    }
+}
+
 #[frame_support::pallet]
 pub mod pallet {
     use crate::{
         error::ZeroPoolError,
-        tx_decoder::{TxDecoder, TxType},
-        verifier::alt_bn128_groth16verify,
+        tx_decoder::{decode_proof, TxDecoder, PROOF_SIZE},
+        verifier::verify,
     };
 
     use super::*;
     use frame_support::{
+        dispatch::PostDispatchInfo,
         pallet_prelude::*,
         sp_runtime::traits::AccountIdConversion,
-        traits::{Currency, ExistenceRequirement},
-        PalletId,
+        traits::{
+            BalanceStatus, Currency, ExistenceRequirement, OnUnbalanced, ReservableCurrency,
+            WithdrawReasons,
+        },
+        transactional, PalletId,
     };
     use frame_system::pallet_prelude::*;
     use sp_core::crypto::ByteArray;
-    use sp_runtime::traits::Verify;
+    use sp_runtime::{
+        traits::{
+            CheckedAdd, CheckedSub, SaturatedConversion, Saturating, UniqueSaturatedInto, Verify,
+            Zero,
+        },
+        FixedPointNumber, FixedU128,
+    };
 
     /// Configure the pallet by specifying the parameters and types on which it depends.
     #[pallet::config]
@@ -75,23 +262,114 @@ pub mod pallet {
         type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
         #[pallet::constant]
         type PalletId: Get<PalletId>;
-        type Currency: Currency<Self::AccountId>;
+        type Currency: ReservableCurrency<Self::AccountId>;
 
         /// Any implementation of OperatorManager. This is used to get the current operator
         /// (relayer). There is a default simple implementation in
         /// `pallet-zeropool::operator`.
         type OperatorManager: OperatorManager<Self::AccountId>;
 
+        /// Screens the depositors and the receivers of withdrawals. Use `()` to allow everyone,
+        /// or `pallet-zeropool::compliance` for admin-managed allow and deny lists.
+        type ComplianceFilter: ComplianceFilter<Self::AccountId, BalanceOf<Self>>;
+
         /// Initial owner of the pallet. The owner is the account that is allowed to manually change
         /// the verification keys.
         #[pallet::constant]
         type InitialOwner: Get<Self::AccountId>;
+
+        /// Origin that is allowed to pause the pool. Meant to be fast-acting, e.g. a security
+        /// council or the owner.
+        type PauseOrigin: EnsureOrigin<Self::Origin>;
+
+        /// Origin that is allowed to unpause the pool and to change the pool parameters.
+        type AdminOrigin: EnsureOrigin<Self::Origin>;
+
+        /// Origin that is allowed to freeze suspicious pending withdrawals.
+        type GuardianOrigin: EnsureOrigin<Self::Origin>;
+
+        /// Fee reserved from a user queueing a forced transaction. Paid to whoever includes it.
+        #[pallet::constant]
+        type ForcedTxFee: Get<BalanceOf<Self>>;
+
+        /// Number of blocks the operators have to include a forced transaction. Once exceeded,
+        /// `transact` only accepts the queued transactions, from any account.
+        #[pallet::constant]
+        type ForcedTxInclusionPeriod: Get<Self::BlockNumber>;
+
+        /// Maximum size of a forced transaction.
+        #[pallet::constant]
+        type MaxForcedTxSize: Get<u32>;
+
+        /// Number of the latest roots a transaction can be proven against. Older roots are
+        /// pruned.
+        #[pallet::constant]
+        type RootHistorySize: Get<u32>;
+
+        /// Token paid out for the energy spent in withdrawals.
+        type RewardCurrency: Currency<Self::AccountId>;
+
+        /// Account the energy rewards are transferred from.
+        #[pallet::constant]
+        type RewardAccount: Get<Self::AccountId>;
+
+        /// Reward paid per unit of energy, in `RewardCurrency` units.
+        #[pallet::constant]
+        type EnergyRewardRate: Get<FixedU128>;
+
+        /// Highest operator rate the native top-up of a withdrawal is exchanged at. The users
+        /// don't sign the rate, so this bounds what an operator can charge them.
+        #[pallet::constant]
+        type MaxNativeRate: Get<FixedU128>;
+
+        /// Handler for the protocol fees, e.g. a treasury account.
+        type Treasury: OnUnbalanced<NegativeImbalanceOf<Self>>;
+
+        /// Weights of the transactions, generated with `scripts/bench`.
+        type WeightInfo: WeightInfo;
+
+        /// Maximum number of direct deposits inserted with a single batch proof.
+        #[pallet::constant]
+        type MaxDirectDepositsPerBatch: Get<u32>;
+
+        /// Number of blocks after which the sender of an unprocessed direct deposit can take it
+        /// back.
+        #[pallet::constant]
+        type DirectDepositTimeout: Get<Self::BlockNumber>;
     }
 
     #[pallet::pallet]
     #[pallet::generate_store(pub(super) trait Store)]
+    #[pallet::storage_version(STORAGE_VERSION)]
     pub struct Pallet<T>(_);
 
+    #[pallet::hooks]
+    impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+        fn on_runtime_upgrade() -> Weight {
+            migrations::migrate::<T>()
+        }
+
+        fn on_idle(_n: BlockNumberFor<T>, remaining_weight: Weight) -> Weight {
+            migrations::v2::prune_roots::<T>(remaining_weight)
+        }
+
+        // Not accounted in the block weight, so only checked in tests and try-runtime builds.
+        #[cfg(any(test, feature = "try-runtime"))]
+        fn on_finalize(_n: BlockNumberFor<T>) {
+            assert!(Self::check_solvency().is_ok(), "The pool is insolvent");
+        }
+
+        #[cfg(feature = "try-runtime")]
+        fn pre_upgrade() -> Result<(), &'static str> {
+            migrations::pre_upgrade::<T>()
+        }
+
+        #[cfg(feature = "try-runtime")]
+        fn post_upgrade() -> Result<(), &'static str> {
+            migrations::post_upgrade::<T>()
+        }
+    }
+
     #[pallet::storage]
     pub type Nullifiers<T> = StorageMap<_, Blake2_128Concat, NativeU256, NativeU256>;
 
@@ -107,15 +385,74 @@ pub mod pallet {
     #[pallet::storage]
     pub type PoolIndex<T> = StorageValue<_, NativeU256, ValueQuery>;
 
+    /// Expired roots left to remove, from the first to the end of the range. Set by
+    /// `migrations::v2` and drained in `on_idle`.
+    #[pallet::storage]
+    pub type RootsToPrune<T> = StorageValue<_, (NativeU256, NativeU256)>;
+
     #[pallet::storage]
     pub type AllMessagesHash<T> = StorageValue<_, NativeU256, ValueQuery>;
 
+    /// Total amount deposited into the pool, in pool token units.
+    #[pallet::storage]
+    pub type TotalDeposited<T> = StorageValue<_, NativeU256, ValueQuery>;
+
+    /// Total amount withdrawn from the pool, in pool token units.
+    #[pallet::storage]
+    pub type TotalWithdrawn<T> = StorageValue<_, NativeU256, ValueQuery>;
+
+    /// Total amount of fees paid to the operators, in pool token units.
+    #[pallet::storage]
+    pub type TotalFees<T> = StorageValue<_, NativeU256, ValueQuery>;
+
+    /// Amount held in the shielded notes: deposits minus withdrawals and fees, in pool token
+    /// units. Only counts the transactions processed since the ledger was introduced.
+    #[pallet::storage]
+    pub type ShieldedSupply<T> = StorageValue<_, NativeU256, ValueQuery>;
+
+    #[pallet::storage]
+    pub type ProtocolFees<T> = StorageValue<_, ProtocolFee, ValueQuery>;
+
+    #[pallet::storage]
+    pub type GlobalFeePolicy<T> = StorageValue<_, FeePolicy, ValueQuery>;
+
+    /// Total protocol fees paid to the treasury.
+    #[pallet::storage]
+    pub type TotalProtocolFees<T: Config> = StorageValue<_, BalanceOf<T>, ValueQuery>;
+
+    /// Total energy withdrawn from the pool.
+    #[pallet::storage]
+    pub type TotalEnergyWithdrawn<T> = StorageValue<_, NativeU256, ValueQuery>;
+
+    /// Total reward paid for the withdrawn energy.
+    #[pallet::storage]
+    pub type TotalEnergyRewards<T: Config> = StorageValue<_, RewardBalanceOf<T>, ValueQuery>;
+
+    /// Output commitments by the pool index of their first leaf. Lets wallets rebuild the
+    /// Merkle tree without replaying the events.
+    #[pallet::storage]
+    pub type Commitments<T> = StorageMap<_, Blake2_128Concat, NativeU256, NativeU256>;
+
     #[pallet::storage]
     pub type TransferVk<T> = StorageValue<_, VK>;
 
     #[pallet::storage]
     pub type TreeVk<T> = StorageValue<_, VK>;
 
+    #[pallet::storage]
+    pub type DirectDepositVk<T> = StorageValue<_, VK>;
+
+    /// Queue of direct deposits, from `DirectDepositHead` to `NextDirectDepositId`.
+    #[pallet::storage]
+    pub type DirectDeposits<T: Config> = StorageMap<_, Twox64Concat, u64, DirectDepositOf<T>>;
+
+    /// Id of the oldest direct deposit that may still be in the queue.
+    #[pallet::storage]
+    pub type DirectDepositHead<T> = StorageValue<_, u64, ValueQuery>;
+
+    #[pallet::storage]
+    pub type NextDirectDepositId<T> = StorageValue<_, u64, ValueQuery>;
+
     #[pallet::type_value]
     pub fn DefaultPoolId<T: Config>() -> NativeU256 {
         FIRST_ROOT.into()
@@ -132,6 +469,51 @@ pub mod pallet {
     #[pallet::storage]
     pub type Owner<T: Config> = StorageValue<_, T::AccountId, ValueQuery, DefaultOwner<T>>;
 
+    /// Account proposed as the new owner, which has yet to accept the ownership.
+    #[pallet::storage]
+    pub type PendingOwner<T: Config> = StorageValue<_, T::AccountId>;
+
+    /// Transaction kinds that are currently rejected by `transact`.
+    #[pallet::storage]
+    pub type Paused<T> = StorageValue<_, TxKinds, ValueQuery>;
+
+    #[pallet::storage]
+    pub type Limits<T: Config> = StorageValue<_, PoolLimitsOf<T>, ValueQuery>;
+
+    /// Total amount withdrawn during the current window.
+    #[pallet::storage]
+    pub type WithdrawWindow<T: Config> = StorageValue<_, WindowUsageOf<T>, ValueQuery>;
+
+    /// Amount withdrawn to each account during its current window.
+    #[pallet::storage]
+    pub type AccountWithdrawWindow<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, WindowUsageOf<T>, ValueQuery>;
+
+    #[pallet::storage]
+    pub type LargeWithdrawalDelay<T: Config> = StorageValue<_, WithdrawalDelayOf<T>, ValueQuery>;
+
+    #[pallet::storage]
+    pub type PendingWithdrawals<T: Config> =
+        StorageMap<_, Twox64Concat, u64, PendingWithdrawalOf<T>>;
+
+    #[pallet::storage]
+    pub type NextWithdrawalId<T> = StorageValue<_, u64, ValueQuery>;
+
+    /// Energy withdrawn along with a pending withdrawal, rewarded when the withdrawal is claimed.
+    #[pallet::storage]
+    pub type PendingEnergyRewards<T> = StorageMap<_, Twox64Concat, u64, NativeU256>;
+
+    /// Queue of forced transactions, from `ForcedTxHead` to `NextForcedTxId`.
+    #[pallet::storage]
+    pub type ForcedTxs<T: Config> = StorageMap<_, Twox64Concat, u64, ForcedTxOf<T>>;
+
+    /// Id of the oldest forced transaction in the queue.
+    #[pallet::storage]
+    pub type ForcedTxHead<T> = StorageValue<_, u64, ValueQuery>;
+
+    #[pallet::storage]
+    pub type NextForcedTxId<T> = StorageValue<_, u64, ValueQuery>;
+
     #[pallet::event]
     #[pallet::generate_deposit(pub(super) fn deposit_event)]
     pub enum Event<T: Config> {
@@ -139,6 +521,48 @@ pub mod pallet {
         Message(NativeU256, NativeU256, NativeU256, Vec<u8>),
         TransferVkSet,
         TreeVkSet,
+        DirectDepositVkSet,
+        /// [id, sender, amount]
+        DirectDepositQueued(u64, T::AccountId, BalanceOf<T>),
+        /// [ids]
+        DirectDepositsProcessed(Vec<u64>),
+        /// [id, sender, amount]
+        DirectDepositRefunded(u64, T::AccountId, BalanceOf<T>),
+        /// [new_owner]
+        OwnershipProposed(T::AccountId),
+        /// [new_owner]
+        OwnerChanged(T::AccountId),
+        /// [kinds]
+        Paused(TxKinds),
+        /// [kinds]
+        Unpaused(TxKinds),
+        /// [limits]
+        LimitsSet(PoolLimitsOf<T>),
+        /// [config]
+        WithdrawalDelaySet(WithdrawalDelayOf<T>),
+        /// [id, dest, amount, unlock_at]
+        WithdrawalQueued(u64, T::AccountId, BalanceOf<T>, T::BlockNumber),
+        /// [id]
+        WithdrawalClaimed(u64),
+        /// [id, who]
+        ForcedTxQueued(u64, T::AccountId),
+        /// [id]
+        ForcedTxIncluded(u64),
+        /// The forced transaction is invalid and was removed from the queue. [id]
+        ForcedTxDropped(u64),
+        /// [id]
+        WithdrawalFrozen(u64),
+        /// [id]
+        WithdrawalThawed(u64),
+        /// The operator sent native tokens to a withdrawing user in exchange for part of the
+        /// withdrawal. [operator, dest, native_amount, cost]
+        NativeTopUp(T::AccountId, T::AccountId, BalanceOf<T>, BalanceOf<T>),
+        /// [dest, energy, reward]
+        EnergyWithdrawn(T::AccountId, NativeU256, RewardBalanceOf<T>),
+        /// [fee]
+        ProtocolFeeSet(ProtocolFee),
+        /// [policy]
+        FeePolicySet(FeePolicy),
     }
 
     #[pallet::error]
@@ -146,6 +570,7 @@ pub mod pallet {
         AltBn128DeserializationError,
         AltBn128SerializationError,
         NotConsistentGroth16InputsError,
+        InvalidProof,
 
         IndexOutOfBounds,
         InsufficientBalance,
@@ -158,7 +583,31 @@ pub mod pallet {
         TransferVkNotSet,
         TreeVkNotSet,
         NotOwner,
+        NotPendingOwner,
         NotOperator,
+        Paused,
+        TvlCapExceeded,
+        TxAmountLimitExceeded,
+        WithdrawWindowLimitExceeded,
+        AccountWithdrawWindowLimitExceeded,
+        WithdrawalNotFound,
+        WithdrawalFrozen,
+        WithdrawalLocked,
+        ForcedTxTooLarge,
+        ForcedTxOverdue,
+        TransferIndexTooOld,
+        WithdrawBelowExistentialDeposit,
+        NativeAmountTooLarge,
+        EnergyAmountTooLarge,
+        InvalidProtocolFee,
+        FeeTooLow,
+        DirectDepositVkNotSet,
+        InvalidBatchSize,
+        DirectDepositNotFound,
+        NotDepositor,
+        DirectDepositNotExpired,
+        DepositNotAllowed,
+        WithdrawalNotAllowed,
     }
 
     impl<T> From<ZeroPoolError> for Error<T> {
@@ -185,15 +634,248 @@ pub mod pallet {
             <Owner<T>>::get()
         }
 
-        fn check_operator(origin: OriginFor<T>) -> Result<T::AccountId, DispatchError> {
-            let who = ensure_signed(origin)?;
-
+        fn check_operator(who: &T::AccountId) -> DispatchResult {
             if !Self::is_operator(who.clone()) {
                 log::warn!("Failed to enforce an operator");
                 return Err(Error::<T>::NotOperator.into())
             }
 
-            Ok(who)
+            Ok(())
+        }
+
+        /// Returns up to `limit` commitments starting from the transaction that `from_index`
+        /// falls into, along with their storage keys. Commitments of the transactions processed
+        /// before the commitment log was introduced are missing, they count against `limit` so
+        /// that a call never reads more than `limit` entries.
+        pub fn commitments(
+            from_index: NativeU256,
+            limit: u32,
+        ) -> Vec<(NativeU256, NativeU256, Vec<u8>)> {
+            let step = NativeU256::from(128u32);
+            let mut index = tx_index(from_index);
+            let end = <PoolIndex<T>>::get().min(index.saturating_add(step * limit));
+            let mut commitments = Vec::new();
+
+            while index < end && commitments.len() < limit as usize {
+                if let Some(commitment) = <Commitments<T>>::get(index) {
+                    commitments.push((index, commitment, <Commitments<T>>::hashed_key_for(index)));
+                }
+                index += step;
+            }
+
+            commitments
+        }
+
+        /// Checks that the pool account holds enough to cover the shielded supply on top of the
+        /// existential deposit that keeps it alive.
+        pub fn check_solvency() -> Result<(), &'static str> {
+            let required = <ShieldedSupply<T>>::get().saturating_mul(DENOMINATOR.into());
+            let balance: u128 = T::Currency::free_balance(&Self::account_id())
+                .saturating_sub(T::Currency::minimum_balance())
+                .unique_saturated_into();
+            if NativeU256::from(balance) < required {
+                log::warn!("Pool balance {} does not cover {}", balance, required);
+                return Err("Pool balance does not cover the shielded supply")
+            }
+
+            Ok(())
+        }
+
+        /// Distance between the current pool index and the oldest root in the history.
+        pub(crate) fn root_history_span() -> U256 {
+            U256::from(T::RootHistorySize::get().max(1) - 1).unchecked_mul(U256::from(128u8))
+        }
+
+        /// Returns the native amount the operator sends to a withdrawing user along with its cost
+        /// in the withdrawn token, at the rate published by the operator. Both are in native
+        /// units. There is no top-up if the operator has no rate or if it's above
+        /// `MaxNativeRate`, the user then receives the whole withdrawal in the pool token.
+        pub(crate) fn native_top_up(
+            operator: &T::AccountId,
+            native_amount: U256,
+            withdraw_amount: U256,
+        ) -> Result<(BalanceOf<T>, BalanceOf<T>), DispatchError> {
+            if native_amount == U256::ZERO {
+                return Ok((Zero::zero(), Zero::zero()))
+            }
+
+            let rate = match <<T as Config>::OperatorManager>::native_rate(operator.clone()) {
+                Some(rate) if rate <= T::MaxNativeRate::get() => rate,
+                _ => {
+                    log::warn!("Skipping the native top-up, the operator rate is not usable");
+                    return Ok((Zero::zero(), Zero::zero()))
+                },
+            };
+            let native_amount: NativeU256 = native_amount.into();
+            if native_amount > NativeU256::from(u128::MAX) {
+                return Err(Error::<T>::NativeAmountTooLarge.into())
+            }
+            let cost = rate
+                .checked_mul_int(native_amount.low_u128())
+                .ok_or(Error::<T>::NativeAmountTooLarge)?;
+            if U256::from(NativeU256::from(cost)) > withdraw_amount {
+                log::warn!("Native top-up costs more than the withdrawal");
+                return Err(Error::<T>::NativeAmountTooLarge.into())
+            }
+
+            let to_balance = |amount: U256| {
+                let encoded_amount = amount.unchecked_mul(DENOMINATOR).encode();
+                <BalanceOf<T>>::decode(&mut &encoded_amount[..])
+                    .map_err(|_err| Into::<DispatchError>::into(Error::<T>::Deserialization))
+            };
+
+            Ok((to_balance(native_amount.into())?, to_balance(NativeU256::from(cost).into())?))
+        }
+
+        /// Converts a direct deposit amount into pool units. It must be a non-zero multiple of
+        /// `DENOMINATOR` that fits into a note.
+        fn direct_deposit_pool_amount(amount: BalanceOf<T>) -> Result<u64, DispatchError> {
+            let amount: u128 = amount.unique_saturated_into();
+            let denominator = NativeU256::from(DENOMINATOR).low_u128();
+            if amount == 0 || amount % denominator != 0 {
+                return Err(Error::<T>::IncorrectAmount.into())
+            }
+
+            u64::try_from(amount / denominator).map_err(|_| Error::<T>::IncorrectAmount.into())
+        }
+
+        /// Withdraws the protocol fee from `who` and hands it over to the treasury.
+        fn pay_protocol_fee(
+            who: &T::AccountId,
+            amount: BalanceOf<T>,
+            liveness: ExistenceRequirement,
+        ) -> DispatchResult {
+            if amount.is_zero() {
+                return Ok(())
+            }
+
+            let imbalance = T::Currency::withdraw(who, amount, WithdrawReasons::FEE, liveness)?;
+            T::Treasury::on_unbalanced(imbalance);
+            <TotalProtocolFees<T>>::mutate(|total| *total = total.saturating_add(amount));
+
+            Ok(())
+        }
+
+        /// Pays the reward for the energy withdrawn to `dest` from `RewardAccount`. Best effort,
+        /// the withdrawal goes through even if `RewardAccount` can't pay.
+        pub(crate) fn reward_energy(dest: &T::AccountId, energy: NativeU256) {
+            let reward: RewardBalanceOf<T> = T::EnergyRewardRate::get()
+                .saturating_mul_int(energy.low_u128())
+                .saturated_into();
+
+            let paid = match T::RewardCurrency::transfer(
+                &T::RewardAccount::get(),
+                dest,
+                reward,
+                ExistenceRequirement::KeepAlive,
+            ) {
+                Ok(()) => reward,
+                Err(err) => {
+                    log::warn!("Failed to pay the energy reward: {:?}", err);
+                    Zero::zero()
+                },
+            };
+
+            <TotalEnergyWithdrawn<T>>::mutate(|total| *total = total.saturating_add(energy));
+            <TotalEnergyRewards<T>>::mutate(|total| *total = total.saturating_add(paid));
+
+            Self::deposit_event(Event::EnergyWithdrawn(dest.clone(), energy, paid));
+        }
+
+        /// Weight of `transact`, by transaction type and memo size. Covers the forced
+        /// transaction queue and the operator checks on top of the benchmarked processing.
+        /// Unknown transactions are charged as withdrawals.
+        pub fn transact_weight(tx_type: Option<TxType>, memo_size: usize) -> Weight {
+            let kind = match tx_type {
+                Some(TxType::Deposit) => T::WeightInfo::transact_deposit(),
+                Some(TxType::Transfer) => T::WeightInfo::transact_transfer(),
+                Some(TxType::Withdraw) | None => T::WeightInfo::transact_withdraw(),
+            };
+            let memo = T::WeightInfo::memo(memo_size.try_into().unwrap_or(u32::MAX));
+
+            kind.saturating_add(memo).saturating_add(T::DbWeight::get().reads(4))
+        }
+
+        /// Weight of `process_direct_deposits` for a batch of `count` deposits. Covers the holes
+        /// the queue scan may skip and the operator checks on top of the benchmarked processing.
+        pub fn direct_deposits_weight(count: u32) -> Weight {
+            let count = count.min(T::MaxDirectDepositsPerBatch::get());
+            let scanned = count.saturating_add(T::MaxDirectDepositsPerBatch::get());
+            let memo = T::WeightInfo::memo(count.saturating_mul(DIRECT_DEPOSIT_SIZE as u32));
+
+            T::WeightInfo::process_direct_deposits()
+                .saturating_add(memo)
+                .saturating_add(T::DbWeight::get().reads_writes(scanned.into(), count.into()))
+                .saturating_add(T::DbWeight::get().reads(4))
+        }
+
+        fn transact_post_info(data: &[u8]) -> PostDispatchInfo {
+            let tx = TxDecoder::new(data);
+            let memo_size = if tx.has_memo_fee() { tx.memo_size() } else { 0 };
+
+            PostDispatchInfo {
+                actual_weight: Some(Self::transact_weight(tx.checked_tx_type(), memo_size)),
+                pays_fee: Pays::No,
+            }
+        }
+
+        /// Rejects a transaction whose fee is below the global fee policy or the fee schedule of
+        /// the submitting operator. Cheap enough to run before the proofs are verified.
+        fn check_fee(operator: &T::AccountId, data: &[u8]) -> DispatchResult {
+            let tx = TxDecoder::new(data);
+            if !tx.has_memo_fee() {
+                return Err(Error::<T>::InvalidTxFormat.into())
+            }
+
+            let memo_size = tx.memo_size() as u32;
+            let min_fee = <GlobalFeePolicy<T>>::get()
+                .fee(memo_size)
+                .max(<<T as Config>::OperatorManager>::min_fee(operator.clone(), memo_size));
+            if tx.memo_fee() < U256::from(min_fee) {
+                log::warn!("Transaction fee is below {}", min_fee);
+                return Err(Error::<T>::FeeTooLow.into())
+            }
+
+            Ok(())
+        }
+
+        fn is_forced_tx_overdue(forced: &ForcedTxOf<T>) -> bool {
+            <frame_system::Pallet<T>>::block_number() >=
+                forced.queued_at.saturating_add(T::ForcedTxInclusionPeriod::get())
+        }
+
+        /// Processes the forced transaction at the head of the queue and pops it. An invalid
+        /// transaction is dropped, the fee goes to the caller either way. A paused transaction
+        /// stays in the queue. Returns whether the transaction was included.
+        fn process_forced_tx(
+            who: T::AccountId,
+            id: u64,
+            forced: ForcedTxOf<T>,
+        ) -> Result<bool, DispatchError> {
+            let result = Self::process_tx(who.clone(), &forced.tx);
+            if let Err(err) = result {
+                if err == Error::<T>::Paused.into() {
+                    return Err(err)
+                }
+            }
+
+            <ForcedTxs<T>>::remove(id);
+            <ForcedTxHead<T>>::put(id.wrapping_add(1));
+
+            T::Currency::repatriate_reserved(&forced.who, &who, forced.fee, BalanceStatus::Free)?;
+
+            match result {
+                Ok(()) => {
+                    <<T as Config>::OperatorManager>::note_transaction(who, true);
+                    Self::deposit_event(Event::ForcedTxIncluded(id));
+                },
+                Err(err) => {
+                    log::warn!("Dropping invalid forced transaction {}: {:?}", id, err);
+                    Self::deposit_event(Event::ForcedTxDropped(id));
+                },
+            }
+
+            Ok(result.is_ok())
         }
 
         fn check_owner(origin: OriginFor<T>) -> Result<T::AccountId, DispatchError> {
@@ -206,6 +888,75 @@ pub mod pallet {
 
             Ok(who)
         }
+
+        fn check_compliance_deposit(who: &T::AccountId, amount: BalanceOf<T>) -> DispatchResult {
+            if !T::ComplianceFilter::can_deposit(who, amount) {
+                log::warn!("Deposit rejected by the compliance filter");
+                return Err(Error::<T>::DepositNotAllowed.into())
+            }
+
+            Ok(())
+        }
+
+        fn check_deposit_limits(amount: BalanceOf<T>) -> DispatchResult {
+            let limits = <Limits<T>>::get();
+
+            if limits.max_tx_amount.map_or(false, |max| amount > max) {
+                return Err(Error::<T>::TxAmountLimitExceeded.into())
+            }
+
+            let tvl = T::Currency::free_balance(&Self::account_id()).saturating_add(amount);
+            if limits.tvl_cap.map_or(false, |cap| tvl > cap) {
+                return Err(Error::<T>::TvlCapExceeded.into())
+            }
+
+            Ok(())
+        }
+
+        /// Checks the withdrawal against the limits and returns the updated window usage for the
+        /// pool and for the destination account. The usage must be stored once the withdrawal
+        /// goes through.
+        fn check_withdraw_limits(
+            dest: &T::AccountId,
+            amount: BalanceOf<T>,
+        ) -> Result<(WindowUsageOf<T>, WindowUsageOf<T>), DispatchError> {
+            let limits = <Limits<T>>::get();
+
+            if limits.max_tx_amount.map_or(false, |max| amount > max) {
+                return Err(Error::<T>::TxAmountLimitExceeded.into())
+            }
+
+            let pool_usage = Self::add_to_window(<WithdrawWindow<T>>::get(), &limits, amount)
+                .filter(|usage| limits.window_limit.map_or(true, |limit| usage.amount <= limit))
+                .ok_or(Error::<T>::WithdrawWindowLimitExceeded)?;
+
+            let account_usage =
+                Self::add_to_window(<AccountWithdrawWindow<T>>::get(dest), &limits, amount)
+                    .filter(|usage| {
+                        limits.account_window_limit.map_or(true, |limit| usage.amount <= limit)
+                    })
+                    .ok_or(Error::<T>::AccountWithdrawWindowLimitExceeded)?;
+
+            Ok((pool_usage, account_usage))
+        }
+
+        /// Adds the amount to the window usage, starting a new window if the current one is over.
+        fn add_to_window(
+            usage: WindowUsageOf<T>,
+            limits: &PoolLimitsOf<T>,
+            amount: BalanceOf<T>,
+        ) -> Option<WindowUsageOf<T>> {
+            let now = <frame_system::Pallet<T>>::block_number();
+
+            let mut usage = if now >= usage.start.saturating_add(limits.withdraw_window) {
+                WindowUsage { start: now, amount: Zero::zero() }
+            } else {
+                usage
+            };
+            usage.amount = usage.amount.checked_add(&amount)?;
+
+            Some(usage)
+        }
     }
 
     #[pallet::call]
@@ -217,12 +968,31 @@ pub mod pallet {
             Ok(())
         }
 
+        /// Proposes a new owner. The ownership is transferred once the proposed account calls
+        /// `accept_ownership`. A new proposal replaces the previous one.
         #[pallet::weight(1000)]
-        pub fn set_owner(origin: OriginFor<T>, address: T::AccountId) -> DispatchResult {
+        pub fn propose_owner(origin: OriginFor<T>, address: T::AccountId) -> DispatchResult {
             Self::check_owner(origin)?;
 
-            <<T as Config>::OperatorManager>::set_owner(address.clone())?;
-            <Owner<T>>::put(address);
+            <PendingOwner<T>>::put(address.clone());
+
+            Self::deposit_event(Event::OwnershipProposed(address));
+
+            Ok(())
+        }
+
+        #[pallet::weight(1000)]
+        pub fn accept_ownership(origin: OriginFor<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            if <PendingOwner<T>>::get().as_ref() != Some(&who) {
+                return Err(Error::<T>::NotPendingOwner.into())
+            }
+
+            <PendingOwner<T>>::kill();
+            <Owner<T>>::put(who.clone());
+
+            Self::deposit_event(Event::OwnerChanged(who));
 
             Ok(())
         }
@@ -231,22 +1001,35 @@ pub mod pallet {
         pub fn set_transfer_vk(origin: OriginFor<T>, data: Vec<u8>) -> DispatchResult {
             Self::check_owner(origin)?;
 
-            let vk = VK::try_from_slice(&data)
+            let vk = Groth16VK::try_from_slice(&data)
                 .map_err(|_err| Into::<DispatchError>::into(Error::<T>::Deserialization))?;
-            <TransferVk<T>>::put(vk);
+            <TransferVk<T>>::put(VK::Groth16(vk));
 
             Self::deposit_event(Event::TransferVkSet);
 
             Ok(())
         }
 
+        #[pallet::weight(1000)]
+        pub fn set_direct_deposit_vk(origin: OriginFor<T>, data: Vec<u8>) -> DispatchResult {
+            Self::check_owner(origin)?;
+
+            let vk = Groth16VK::try_from_slice(&data)
+                .map_err(|_err| Into::<DispatchError>::into(Error::<T>::Deserialization))?;
+            <DirectDepositVk<T>>::put(VK::Groth16(vk));
+
+            Self::deposit_event(Event::DirectDepositVkSet);
+
+            Ok(())
+        }
+
         #[pallet::weight(1000)]
         pub fn set_tree_vk(origin: OriginFor<T>, data: Vec<u8>) -> DispatchResult {
             Self::check_owner(origin)?;
 
-            let vk = VK::try_from_slice(&data)
+            let vk = Groth16VK::try_from_slice(&data)
                 .map_err(|_err| Into::<DispatchError>::into(Error::<T>::Deserialization))?;
-            <TreeVk<T>>::put(vk);
+            <TreeVk<T>>::put(VK::Groth16(vk));
 
             Self::deposit_event(Event::TreeVkSet);
 
@@ -254,17 +1037,398 @@ pub mod pallet {
         }
 
         #[pallet::weight(1000)]
-        pub fn transact(origin: OriginFor<T>, data: Vec<u8>) -> DispatchResult {
-            let operator = Self::check_operator(origin)?;
+        pub fn pause(origin: OriginFor<T>, kinds: TxKinds) -> DispatchResult {
+            T::PauseOrigin::ensure_origin(origin)?;
+
+            <Paused<T>>::mutate(|paused| *paused = paused.union(kinds));
+
+            log::warn!("Pool paused: {:?}", kinds);
+            Self::deposit_event(Event::Paused(kinds));
+
+            Ok(())
+        }
+
+        #[pallet::weight(1000)]
+        pub fn unpause(origin: OriginFor<T>, kinds: TxKinds) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
 
+            <Paused<T>>::mutate(|paused| *paused = paused.difference(kinds));
+
+            Self::deposit_event(Event::Unpaused(kinds));
+
+            Ok(())
+        }
+
+        #[pallet::weight(1000)]
+        pub fn set_limits(origin: OriginFor<T>, limits: PoolLimitsOf<T>) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            <Limits<T>>::put(limits.clone());
+
+            Self::deposit_event(Event::LimitsSet(limits));
+
+            Ok(())
+        }
+
+        #[pallet::weight(1000)]
+        pub fn set_protocol_fee(origin: OriginFor<T>, fee: ProtocolFee) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            if fee.amount_bps > ProtocolFee::MAX_BPS {
+                return Err(Error::<T>::InvalidProtocolFee.into())
+            }
+
+            <ProtocolFees<T>>::put(fee);
+
+            Self::deposit_event(Event::ProtocolFeeSet(fee));
+
+            Ok(())
+        }
+
+        #[pallet::weight(1000)]
+        pub fn set_fee_policy(origin: OriginFor<T>, policy: FeePolicy) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            <GlobalFeePolicy<T>>::put(policy);
+
+            Self::deposit_event(Event::FeePolicySet(policy));
+
+            Ok(())
+        }
+
+        #[pallet::weight(1000)]
+        pub fn set_withdrawal_delay(
+            origin: OriginFor<T>,
+            config: WithdrawalDelayOf<T>,
+        ) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            <LargeWithdrawalDelay<T>>::put(config.clone());
+
+            Self::deposit_event(Event::WithdrawalDelaySet(config));
+
+            Ok(())
+        }
+
+        /// Pays out a pending withdrawal once its delay is over. Can be called by anyone.
+        /// The pause state and the compliance filter are checked again, since either could
+        /// have changed during the delay.
+        #[pallet::weight(1000)]
+        pub fn claim_withdrawal(origin: OriginFor<T>, id: u64) -> DispatchResult {
+            ensure_signed(origin)?;
+
+            let pending = <PendingWithdrawals<T>>::get(id).ok_or(Error::<T>::WithdrawalNotFound)?;
+
+            if pending.frozen {
+                return Err(Error::<T>::WithdrawalFrozen.into())
+            }
+
+            if <frame_system::Pallet<T>>::block_number() < pending.unlock_at {
+                return Err(Error::<T>::WithdrawalLocked.into())
+            }
+
+            if <Paused<T>>::get().contains(&TxType::Withdraw) {
+                return Err(Error::<T>::Paused.into())
+            }
+
+            if !T::ComplianceFilter::can_withdraw(&pending.dest, pending.amount) {
+                return Err(Error::<T>::WithdrawalNotAllowed.into())
+            }
+
+            T::Currency::transfer(
+                &Self::account_id(),
+                &pending.dest,
+                pending.amount,
+                ExistenceRequirement::KeepAlive,
+            )?;
+
+            <PendingWithdrawals<T>>::remove(id);
+
+            if let Some(energy) = <PendingEnergyRewards<T>>::take(id) {
+                Self::reward_energy(&pending.dest, energy);
+            }
+
+            Self::deposit_event(Event::WithdrawalClaimed(id));
+
+            Ok(())
+        }
+
+        #[pallet::weight(1000)]
+        pub fn freeze_withdrawal(origin: OriginFor<T>, id: u64) -> DispatchResult {
+            T::GuardianOrigin::ensure_origin(origin)?;
+
+            <PendingWithdrawals<T>>::try_mutate(id, |pending| -> DispatchResult {
+                let pending = pending.as_mut().ok_or(Error::<T>::WithdrawalNotFound)?;
+                pending.frozen = true;
+                Ok(())
+            })?;
+
+            log::warn!("Pending withdrawal {} frozen", id);
+            Self::deposit_event(Event::WithdrawalFrozen(id));
+
+            Ok(())
+        }
+
+        #[pallet::weight(1000)]
+        pub fn thaw_withdrawal(origin: OriginFor<T>, id: u64) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            <PendingWithdrawals<T>>::try_mutate(id, |pending| -> DispatchResult {
+                let pending = pending.as_mut().ok_or(Error::<T>::WithdrawalNotFound)?;
+                pending.frozen = false;
+                Ok(())
+            })?;
+
+            Self::deposit_event(Event::WithdrawalThawed(id));
+
+            Ok(())
+        }
+
+        /// Queues a transaction that the operators must include within
+        /// `ForcedTxInclusionPeriod` blocks. Reserves `ForcedTxFee` from the caller.
+        #[pallet::weight(1000)]
+        pub fn queue_forced_tx(origin: OriginFor<T>, tx: Vec<u8>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            if !TxDecoder::new(&tx).is_valid() {
+                return Err(Error::<T>::InvalidTxFormat.into())
+            }
+
+            let tx: BoundedVec<u8, T::MaxForcedTxSize> =
+                tx.try_into().map_err(|_| Error::<T>::ForcedTxTooLarge)?;
+            let fee = T::ForcedTxFee::get();
+            T::Currency::reserve(&who, fee)?;
+
+            let id = <NextForcedTxId<T>>::get();
+            <ForcedTxs<T>>::insert(
+                id,
+                ForcedTx {
+                    who: who.clone(),
+                    fee,
+                    queued_at: <frame_system::Pallet<T>>::block_number(),
+                    tx,
+                },
+            );
+            <NextForcedTxId<T>>::put(id.wrapping_add(1));
+
+            Self::deposit_event(Event::ForcedTxQueued(id, who));
+
+            Ok(())
+        }
+
+        /// Locks `amount` in the pool and queues a note for `receiver`, no proof is needed from
+        /// the sender. The protocol fee is charged on top of `amount`, like for deposits. The
+        /// operators insert the queued deposits with `process_direct_deposits`.
+        #[pallet::weight(1000)]
+        #[transactional]
+        pub fn direct_deposit(
+            origin: OriginFor<T>,
+            receiver: ZkAddress,
+            amount: BalanceOf<T>,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            if <Paused<T>>::get().contains(&TxType::Deposit) {
+                return Err(Error::<T>::Paused.into())
+            }
+
+            Self::direct_deposit_pool_amount(amount)?;
+            Self::check_compliance_deposit(&who, amount)?;
+            Self::check_deposit_limits(amount)?;
+
+            let protocol_fee = <ProtocolFees<T>>::get().amount_fee(amount);
+            Self::pay_protocol_fee(&who, protocol_fee, ExistenceRequirement::AllowDeath)?;
+            T::Currency::transfer(
+                &who,
+                &Self::account_id(),
+                amount,
+                ExistenceRequirement::AllowDeath,
+            )?;
+
+            let id = <NextDirectDepositId<T>>::get();
+            <DirectDeposits<T>>::insert(
+                id,
+                DirectDeposit {
+                    sender: who.clone(),
+                    receiver,
+                    amount,
+                    created_at: <frame_system::Pallet<T>>::block_number(),
+                },
+            );
+            <NextDirectDepositId<T>>::put(id.wrapping_add(1));
+
+            Self::deposit_event(Event::DirectDepositQueued(id, who, amount));
+
+            Ok(())
+        }
+
+        /// Inserts the next `count` queued direct deposits into the pool as a single commitment.
+        ///
+        /// `batch_proof` proves that `out_commit` holds the notes of the deposits. Its public
+        /// input is the keccak hash of `out_commit` followed by the deposits as they appear in
+        /// the memo of the `Message` event, modulo the field size. `tree_proof` proves the
+        /// insertion of `out_commit` like in `transact`. Both proofs use the transaction
+        /// encoding.
+        #[pallet::weight(Pallet::<T>::direct_deposits_weight(*count))]
+        pub fn process_direct_deposits(
+            origin: OriginFor<T>,
+            count: u32,
+            out_commit: NativeU256,
+            root_after: NativeU256,
+            batch_proof: Vec<u8>,
+            tree_proof: Vec<u8>,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+            Self::check_operator(&who)?;
+
+            if let Some(forced) = <ForcedTxs<T>>::get(<ForcedTxHead<T>>::get()) {
+                if Self::is_forced_tx_overdue(&forced) {
+                    return Err(Error::<T>::ForcedTxOverdue.into())
+                }
+            }
+
+            if <Paused<T>>::get().contains(&TxType::Deposit) {
+                return Err(Error::<T>::Paused.into())
+            }
+
+            if count == 0 || count > T::MaxDirectDepositsPerBatch::get() {
+                return Err(Error::<T>::InvalidBatchSize.into())
+            }
+
+            if batch_proof.len() != PROOF_SIZE || tree_proof.len() != PROOF_SIZE {
+                return Err(Error::<T>::InvalidTxFormat.into())
+            }
+
+            Self::process_direct_deposit_batch(
+                count,
+                out_commit,
+                root_after,
+                &batch_proof,
+                &tree_proof,
+            )?;
+            <<T as Config>::OperatorManager>::note_transaction(who, false);
+
+            Ok(())
+        }
+
+        /// Returns a direct deposit that was not processed within `DirectDepositTimeout` blocks
+        /// to its sender, and moves `DirectDepositHead` past up to `MaxDirectDepositsPerBatch`
+        /// refunded deposits.
+        #[pallet::weight(
+            1000 + T::DbWeight::get().reads_writes(T::MaxDirectDepositsPerBatch::get().into(), 1)
+        )]
+        pub fn refund_direct_deposit(origin: OriginFor<T>, id: u64) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            let deposit = <DirectDeposits<T>>::get(id).ok_or(Error::<T>::DirectDepositNotFound)?;
+
+            if deposit.sender != who {
+                return Err(Error::<T>::NotDepositor.into())
+            }
+
+            if <frame_system::Pallet<T>>::block_number() <
+                deposit.created_at.saturating_add(T::DirectDepositTimeout::get())
+            {
+                return Err(Error::<T>::DirectDepositNotExpired.into())
+            }
+
+            T::Currency::transfer(
+                &Self::account_id(),
+                &who,
+                deposit.amount,
+                ExistenceRequirement::KeepAlive,
+            )?;
+
+            <DirectDeposits<T>>::remove(id);
+
+            let mut head = <DirectDepositHead<T>>::get();
+            let end = <NextDirectDepositId<T>>::get()
+                .min(head.saturating_add(T::MaxDirectDepositsPerBatch::get().into()));
+            while head < end && !<DirectDeposits<T>>::contains_key(head) {
+                head += 1;
+            }
+            <DirectDepositHead<T>>::put(head);
+
+            Self::deposit_event(Event::DirectDepositRefunded(id, who, deposit.amount));
+
+            Ok(())
+        }
+
+        /// Processes a transaction. Only operators can submit new transactions, but the forced
+        /// transaction at the head of the queue is accepted from anyone once it is overdue, and
+        /// nothing else is accepted until the overdue transactions are processed.
+        ///
+        /// Successful transactions are free, the operator is paid through the memo fee. The caller
+        /// pays for dropping an invalid forced transaction, and receives its `ForcedTxFee`.
+        #[pallet::weight(Pallet::<T>::transact_weight(
+            TxDecoder::new(data).checked_tx_type(),
+            data.len(),
+        ))]
+        pub fn transact(origin: OriginFor<T>, data: Vec<u8>) -> DispatchResultWithPostInfo {
+            let who = ensure_signed(origin)?;
+
+            let head = <ForcedTxHead<T>>::get();
+            if let Some(forced) = <ForcedTxs<T>>::get(head) {
+                let overdue = Self::is_forced_tx_overdue(&forced);
+
+                if forced.tx[..] == data[..] {
+                    if !overdue {
+                        Self::check_operator(&who)?;
+                    }
+
+                    let mut info = Self::transact_post_info(&data);
+                    // Dropping an invalid transaction is paid for, out of its `ForcedTxFee`
+                    if !Self::process_forced_tx(who, head, forced)? {
+                        info.pays_fee = Pays::Yes;
+                    }
+                    return Ok(info)
+                }
+
+                if overdue {
+                    log::warn!("Forced transaction {} is overdue", head);
+                    return Err(Error::<T>::ForcedTxOverdue.into())
+                }
+            }
+
+            Self::check_operator(&who)?;
+            // Forced transactions are exempt, their inclusion is paid with `ForcedTxFee`
+            Self::check_fee(&who, &data)?;
+
+            Self::process_tx(who.clone(), &data)?;
+            <<T as Config>::OperatorManager>::note_transaction(who, false);
+
+            Ok(Self::transact_post_info(&data))
+        }
+    }
+
+    impl<T: Config> Pallet<T> {
+        /// Verifies and applies a transaction. Storage changes are reverted on error.
+        #[transactional]
+        pub(crate) fn process_tx(operator: T::AccountId, data: &[u8]) -> DispatchResult {
             log::info!("Processing ZeroPool transaction");
 
-            let tx = TxDecoder::new(data.as_slice());
+            let tx = TxDecoder::new(data);
+
+            if !tx.is_valid() {
+                log::warn!("Invalid transaction format");
+                return Err(Error::<T>::InvalidTxFormat.into())
+            }
+
+            if <Paused<T>>::get().contains(&tx.tx_type()) {
+                log::warn!("Transaction kind is paused");
+                return Err(Error::<T>::Paused.into())
+            }
+
             let message_hash = keccak_256(tx.memo_message());
             let message_hash_num = U256::from_little_endian(&message_hash).unchecked_rem(R);
-            let mut pool_index: U256 = <PoolIndex<T>>::get().into();
+            let pool_index: U256 = <PoolIndex<T>>::get().into();
             let pool_root: U256 = <Roots<T>>::get::<NativeU256>(pool_index.into()).into();
 
+            if tx.transfer_index().unchecked_add(Self::root_history_span()) < pool_index {
+                log::warn!("Transfer index is out of the root history");
+                return Err(Error::<T>::TransferIndexTooOld.into())
+            }
+
             // Verify transfer proof
             log::debug!("Verifying transfer proof:");
             let transfer_vk = <TransferVk<T>>::get().ok_or(Error::<T>::TransferVkNotSet)?;
@@ -272,12 +1436,19 @@ pub mod pallet {
             const DELTA_SIZE: u32 = 256;
             let delta = tx.delta().unchecked_add(pool_id.unchecked_shr(DELTA_SIZE));
             log::debug!("    Preparing data");
-            let root_before: U256 = <Roots<T>>::get::<NativeU256>(tx.transfer_index().into()).into();
+            let root_before: U256 =
+                <Roots<T>>::get::<NativeU256>(tx.transfer_index().into()).into();
             let transact_inputs =
                 [root_before, tx.nullifier().into(), tx.out_commit(), delta, message_hash_num];
             log::debug!("    Verification");
-            alt_bn128_groth16verify(&transfer_vk, &tx.transact_proof(), &transact_inputs)
-                .map_err(|err| Into::<Error<T>>::into(err))?;
+            // The transaction format only carries Groth16 proofs for now.
+            let transact_proof = tx.transact_proof();
+            if !verify(&transfer_vk, &transact_proof, &transact_inputs)
+                .map_err(|err| Into::<Error<T>>::into(err))?
+            {
+                log::warn!("Invalid transfer proof");
+                return Err(Error::<T>::InvalidProof.into())
+            }
 
             if <Nullifiers<T>>::contains_key::<NativeU256>(tx.nullifier().into()) {
                 log::warn!("Double spend");
@@ -295,8 +1466,13 @@ pub mod pallet {
             log::debug!("    Preparing data");
             let tree_inputs = [pool_root, tx.root_after(), tx.out_commit()];
             log::debug!("    Verification");
-            alt_bn128_groth16verify(&tree_vk, &tx.tree_proof(), &tree_inputs)
-                .map_err(|err| Into::<Error<T>>::into(err))?;
+            let tree_proof = tx.tree_proof();
+            if !verify(&tree_vk, &tree_proof, &tree_inputs)
+                .map_err(|err| Into::<Error<T>>::into(err))?
+            {
+                log::warn!("Invalid tree proof");
+                return Err(Error::<T>::InvalidProof.into())
+            }
 
             // Set the nullifier
             log::debug!("Updating state");
@@ -309,16 +1485,6 @@ pub mod pallet {
             });
             let hash = U256::from_big_endian(&keccak_256(&elements));
 
-            pool_index = U256::from(pool_index).unchecked_add(U256::from(128u8));
-
-            // Calculate all_messages_hash
-            log::debug!("Updating all_messages_hash");
-            let mut hashes = [0u8; 32 * 2];
-            let all_messages_hash = <AllMessagesHash<T>>::get();
-            all_messages_hash.using_encoded(|data| hashes[..32].copy_from_slice(data));
-            hashes[32..].copy_from_slice(&message_hash);
-            let new_all_messages_hash = U256::from_big_endian(&keccak_256(&hashes));
-
             let fee = tx.memo_fee();
             let token_amount = tx.token_amount().overflowing_add(fee).0;
             let energy_amount = tx.energy_amount();
@@ -329,6 +1495,7 @@ pub mod pallet {
                     if token_amount != U256::ZERO || energy_amount != U256::ZERO {
                         return Err(Error::<T>::IncorrectAmount.into())
                     }
+                    // Only the fee leaves the shielded supply, it is accounted below
                 },
                 TxType::Deposit => {
                     log::debug!("Processing deposit:");
@@ -367,6 +1534,12 @@ pub mod pallet {
                     let native_amount = <BalanceOf<T>>::decode(&mut &encoded_amount[..])
                         .map_err(|_err| Into::<DispatchError>::into(Error::<T>::Deserialization))?;
 
+                    log::debug!("    Screening the depositor");
+                    Self::check_compliance_deposit(&src, native_amount)?;
+
+                    log::debug!("    Checking limits");
+                    Self::check_deposit_limits(native_amount)?;
+
                     log::debug!("    Transferring to the pool");
                     T::Currency::transfer(
                         &src,
@@ -374,6 +1547,16 @@ pub mod pallet {
                         native_amount,
                         ExistenceRequirement::AllowDeath,
                     )?;
+
+                    let protocol_fee = <ProtocolFees<T>>::get().amount_fee(native_amount);
+                    Self::pay_protocol_fee(&src, protocol_fee, ExistenceRequirement::AllowDeath)?;
+
+                    <TotalDeposited<T>>::mutate(|total| {
+                        *total = total.saturating_add(token_amount.into())
+                    });
+                    <ShieldedSupply<T>>::mutate(|supply| {
+                        *supply = supply.saturating_add(token_amount.into())
+                    });
                 },
                 TxType::Withdraw => {
                     log::debug!("Processing withdraw:");
@@ -383,28 +1566,293 @@ pub mod pallet {
                         .map_err(|_err| Into::<DispatchError>::into(Error::<T>::Deserialization))?;
 
                     log::debug!("    Preparing amounts");
-                    let encoded_amount =
-                        (token_amount.overflowing_neg().0.unchecked_mul(DENOMINATOR)).encode();
+                    let amount = token_amount.overflowing_neg().0;
+                    let encoded_amount = (amount.unchecked_mul(DENOMINATOR)).encode();
                     let native_amount = <BalanceOf<T>>::decode(&mut &encoded_amount[..])
                         .map_err(|_err| Into::<DispatchError>::into(Error::<T>::Deserialization))?;
 
-                    log::debug!("    Transferring to the destination address");
-                    T::Currency::transfer(
+                    log::debug!("    Screening the receiver");
+                    if !T::ComplianceFilter::can_withdraw(&dest, native_amount) {
+                        log::warn!("Withdrawal rejected by the compliance filter");
+                        return Err(Error::<T>::WithdrawalNotAllowed.into())
+                    }
+
+                    log::debug!("    Preparing the native top-up");
+                    let (top_up, top_up_cost) =
+                        Self::native_top_up(&operator, tx.memo_native_amount(), amount)?;
+                    let protocol_fee = <ProtocolFees<T>>::get().amount_fee(native_amount);
+                    let payout = native_amount
+                        .checked_sub(&top_up_cost.saturating_add(protocol_fee))
+                        .ok_or(Error::<T>::NativeAmountTooLarge)?;
+
+                    let delay = <LargeWithdrawalDelay<T>>::get();
+                    let queued =
+                        delay.threshold.map_or(false, |threshold| native_amount > threshold);
+
+                    // Creating the destination account takes at least the existential deposit,
+                    // a queued payout is checked now so that it can't get stuck at claim time
+                    let received = payout.saturating_add(top_up);
+                    if !received.is_zero() &&
+                        received < T::Currency::minimum_balance() &&
+                        T::Currency::total_balance(&dest).is_zero()
+                    {
+                        log::warn!("Withdrawal is below the existential deposit");
+                        return Err(Error::<T>::WithdrawBelowExistentialDeposit.into())
+                    }
+
+                    log::debug!("    Checking limits");
+                    let (pool_usage, account_usage) =
+                        Self::check_withdraw_limits(&dest, native_amount)?;
+
+                    let pending_id = if queued {
+                        log::debug!("    Queueing the withdrawal");
+                        let id = <NextWithdrawalId<T>>::get();
+                        let unlock_at =
+                            <frame_system::Pallet<T>>::block_number().saturating_add(delay.delay);
+                        <PendingWithdrawals<T>>::insert(
+                            id,
+                            PendingWithdrawal {
+                                dest: dest.clone(),
+                                amount: payout,
+                                unlock_at,
+                                frozen: false,
+                            },
+                        );
+                        <NextWithdrawalId<T>>::put(id.wrapping_add(1));
+
+                        Self::deposit_event(Event::WithdrawalQueued(
+                            id,
+                            dest.clone(),
+                            payout,
+                            unlock_at,
+                        ));
+
+                        Some(id)
+                    } else {
+                        log::debug!("    Transferring to the destination address");
+                        T::Currency::transfer(
+                            &Self::account_id(),
+                            &dest,
+                            payout,
+                            ExistenceRequirement::KeepAlive,
+                        )?;
+
+                        None
+                    };
+
+                    Self::pay_protocol_fee(
                         &Self::account_id(),
-                        &dest,
-                        native_amount,
-                        ExistenceRequirement::AllowDeath,
+                        protocol_fee,
+                        ExistenceRequirement::KeepAlive,
                     )?;
+
+                    if !top_up.is_zero() {
+                        log::debug!("    Exchanging the native top-up with the operator");
+                        T::Currency::transfer(
+                            &Self::account_id(),
+                            &operator,
+                            top_up_cost,
+                            ExistenceRequirement::KeepAlive,
+                        )?;
+                        T::Currency::transfer(
+                            &operator,
+                            &dest,
+                            top_up,
+                            ExistenceRequirement::KeepAlive,
+                        )?;
+
+                        Self::deposit_event(Event::NativeTopUp(
+                            operator.clone(),
+                            dest.clone(),
+                            top_up,
+                            top_up_cost,
+                        ));
+                    }
+
+                    <WithdrawWindow<T>>::put(pool_usage);
+                    <AccountWithdrawWindow<T>>::insert(&dest, account_usage);
+
+                    <TotalWithdrawn<T>>::mutate(|total| {
+                        *total = total.saturating_add(amount.into())
+                    });
+                    <ShieldedSupply<T>>::mutate(|supply| {
+                        *supply = supply.saturating_sub(amount.into())
+                    });
+
+                    // Energy can only be withdrawn
+                    if energy_amount != U256::ZERO {
+                        if energy_amount <= U256::MAX.unchecked_div(U256::from(2u32)) {
+                            return Err(Error::<T>::IncorrectAmount.into())
+                        }
+
+                        let energy: NativeU256 = energy_amount.overflowing_neg().0.into();
+                        if energy > NativeU256::from(u128::MAX) {
+                            return Err(Error::<T>::EnergyAmountTooLarge.into())
+                        }
+
+                        match pending_id {
+                            Some(id) => {
+                                log::debug!("    Queueing the energy reward");
+                                <PendingEnergyRewards<T>>::insert(id, energy);
+                            },
+                            None => {
+                                log::debug!("    Rewarding the energy");
+                                Self::reward_energy(&dest, energy);
+                            },
+                        }
+                    }
                 },
             }
 
+            if fee > U256::ZERO {
+                log::debug!("    Processing fee");
+                let encoded_fee = fee.unchecked_mul(DENOMINATOR).encode();
+                let native_fee = <BalanceOf<T>>::decode(&mut &encoded_fee[..])
+                    .map_err(|_err| Into::<DispatchError>::into(Error::<T>::Deserialization))?;
+                let protocol_fee = <ProtocolFees<T>>::get().operator_fee_share * native_fee;
+
+                T::Currency::transfer(
+                    &Self::account_id(),
+                    &operator,
+                    native_fee.saturating_sub(protocol_fee),
+                    ExistenceRequirement::KeepAlive,
+                )?;
+                Self::pay_protocol_fee(
+                    &Self::account_id(),
+                    protocol_fee,
+                    ExistenceRequirement::KeepAlive,
+                )?;
+
+                <TotalFees<T>>::mutate(|total| *total = total.saturating_add(fee.into()));
+                <ShieldedSupply<T>>::mutate(|supply| *supply = supply.saturating_sub(fee.into()));
+            }
+
+            log::debug!("Updating state");
+
+            <Nullifiers<T>>::insert::<NativeU256, NativeU256>(tx.nullifier().into(), hash.into());
+            Self::append_commitment(
+                tx.out_commit(),
+                tx.root_after(),
+                &message_hash,
+                tx.ciphertext(),
+            );
+
+            log::info!("Transaction processed successfully");
+
+            Ok(())
+        }
+
+        /// Collects the next `count` direct deposits, verifies the batch and applies it. Storage
+        /// changes are reverted on error.
+        #[transactional]
+        pub(crate) fn process_direct_deposit_batch(
+            count: u32,
+            out_commit: NativeU256,
+            root_after: NativeU256,
+            batch_proof: &[u8],
+            tree_proof: &[u8],
+        ) -> DispatchResult {
+            log::debug!("Collecting the direct deposits");
+            let mut ids = Vec::new();
+            let mut memo = Vec::with_capacity(count as usize * DIRECT_DEPOSIT_SIZE);
+            let mut total = 0u128;
+            let mut id = <DirectDepositHead<T>>::get();
+            // Refunded deposits leave holes in the queue, up to `MaxDirectDepositsPerBatch` of
+            // them are skipped
+            let end = <NextDirectDepositId<T>>::get().min(
+                id.saturating_add(count.saturating_add(T::MaxDirectDepositsPerBatch::get()).into()),
+            );
+            while id < end && ids.len() < count as usize {
+                if let Some(deposit) = <DirectDeposits<T>>::get(id) {
+                    let amount = Self::direct_deposit_pool_amount(deposit.amount)?;
+                    memo.extend_from_slice(&deposit.receiver.diversifier);
+                    memo.extend_from_slice(&deposit.receiver.pk);
+                    memo.extend_from_slice(&amount.to_be_bytes());
+                    total = total.saturating_add(amount.into());
+                    ids.push(id);
+                }
+                id += 1;
+            }
+
+            if ids.len() != count as usize {
+                return Err(Error::<T>::InvalidBatchSize.into())
+            }
+
+            log::debug!("Verifying batch deposit proof");
+            let direct_deposit_vk =
+                <DirectDepositVk<T>>::get().ok_or(Error::<T>::DirectDepositVkNotSet)?;
+            let mut preimage = [0u8; 32].to_vec();
+            out_commit.to_big_endian(&mut preimage[..32]);
+            preimage.extend_from_slice(&memo);
+            let deposits_hash = U256::from_big_endian(&keccak_256(&preimage)).unchecked_rem(R);
+            let batch_proof = decode_proof(batch_proof);
+            if !verify(&direct_deposit_vk, &batch_proof, &[deposits_hash])
+                .map_err(|err| Into::<Error<T>>::into(err))?
+            {
+                log::warn!("Invalid batch deposit proof");
+                return Err(Error::<T>::InvalidProof.into())
+            }
+
+            log::debug!("Verifying tree proof");
+            let tree_vk = <TreeVk<T>>::get().ok_or(Error::<T>::TreeVkNotSet)?;
+            let pool_root: U256 = <Roots<T>>::get(<PoolIndex<T>>::get()).into();
+            let tree_inputs = [pool_root, root_after.into(), out_commit.into()];
+            let tree_proof = decode_proof(tree_proof);
+            if !verify(&tree_vk, &tree_proof, &tree_inputs)
+                .map_err(|err| Into::<Error<T>>::into(err))?
+            {
+                log::warn!("Invalid tree proof");
+                return Err(Error::<T>::InvalidProof.into())
+            }
+
+            log::debug!("Updating state");
+            for id in ids.iter() {
+                <DirectDeposits<T>>::remove(id);
+            }
+            <DirectDepositHead<T>>::put(id);
+
+            let total = NativeU256::from(total);
+            <TotalDeposited<T>>::mutate(|deposited| *deposited = deposited.saturating_add(total));
+            <ShieldedSupply<T>>::mutate(|supply| *supply = supply.saturating_add(total));
+
+            Self::append_commitment(
+                out_commit.into(),
+                root_after.into(),
+                &keccak_256(&memo),
+                &memo,
+            );
+
+            Self::deposit_event(Event::DirectDepositsProcessed(ids));
+
+            Ok(())
+        }
+
+        /// Inserts the commitment of a processed transaction into the tree and publishes its
+        /// memo.
+        pub(crate) fn append_commitment(
+            out_commit: U256,
+            root_after: U256,
+            message_hash: &[u8; 32],
+            memo: &[u8],
+        ) {
+            let commitment_index: U256 = <PoolIndex<T>>::get().into();
+            let pool_index = commitment_index.unchecked_add(U256::from(128u8));
+
+            // Calculate all_messages_hash
+            log::debug!("Updating all_messages_hash");
+            let mut hashes = [0u8; 32 * 2];
+            let all_messages_hash = <AllMessagesHash<T>>::get();
+            all_messages_hash.using_encoded(|data| hashes[..32].copy_from_slice(data));
+            hashes[32..].copy_from_slice(message_hash);
+            let new_all_messages_hash = U256::from_big_endian(&keccak_256(&hashes));
+
             // TODO: Find a less irritating way to create an indexed event.
             log::debug!("Emitting event");
             let event = Event::Message(
                 pool_index.into(),
                 new_all_messages_hash.into(),
-                tx.out_commit().into(),
-                tx.ciphertext().to_vec(),
+                out_commit.into(),
+                memo.to_vec(),
             );
 
             let event = <<T as Config>::Event as From<Event<T>>>::from(event);
@@ -417,30 +1865,22 @@ pub mod pallet {
                 event,
             );
 
-            if fee > U256::ZERO {
-                log::debug!("    Processing fee");
-                let encoded_fee = (fee.unchecked_mul(DENOMINATOR).overflowing_neg().0).encode();
-                let native_fee = <BalanceOf<T>>::decode(&mut &encoded_fee[..])
-                    .map_err(|_err| Into::<DispatchError>::into(Error::<T>::Deserialization))?;
-
-                T::Currency::transfer(
-                    &Self::account_id(),
-                    &operator,
-                    native_fee,
-                    ExistenceRequirement::AllowDeath,
-                )?;
+            <PoolIndex<T>>::put::<NativeU256>(pool_index.into());
+            <Roots<T>>::insert::<NativeU256, NativeU256>(pool_index.into(), root_after.into());
+            let history_end = Self::root_history_span().unchecked_add(U256::from(128u8));
+            if pool_index > history_end {
+                <Roots<T>>::remove::<NativeU256>(pool_index.unchecked_sub(history_end).into());
             }
+            <Commitments<T>>::insert::<NativeU256, NativeU256>(
+                commitment_index.into(),
+                out_commit.into(),
+            );
 
-            log::debug!("Updating state");
-
-            <PoolIndex<T>>::put::<NativeU256>(pool_index.into());
-            <Roots<T>>::insert::<NativeU256, NativeU256>(pool_index.into(), tx.root_after().into());
-            <Nullifiers<T>>::insert::<NativeU256, NativeU256>(tx.nullifier().into(), hash.into());
+            // Keep the memos in the offchain database, events are not available on pruned nodes
+            let memo: (NativeU256, NativeU256, &[u8]) =
+                (commitment_index.into(), out_commit.into(), memo);
+            sp_io::offchain_index::set(&memo_key(commitment_index.into()), &memo.encode());
             <AllMessagesHash<T>>::put::<NativeU256>(new_all_messages_hash.into());
-
-            log::info!("Transaction processed successfully");
-
-            Ok(())
         }
     }
 }
@@ -0,0 +1,165 @@
+use crate::{Config, Pallet, STORAGE_VERSION};
+use frame_support::{
+    traits::{Get, GetStorageVersion},
+    weights::Weight,
+};
+
+/// Runs every migration the on-chain storage version has not gone through yet.
+pub fn migrate<T: Config>() -> Weight {
+    let on_chain = <Pallet<T>>::on_chain_storage_version();
+    let mut weight = T::DbWeight::get().reads(1);
+
+    if on_chain < 1 {
+        weight = weight.saturating_add(v1::migrate::<T>());
+    }
+    if on_chain < 2 {
+        weight = weight.saturating_add(v2::migrate::<T>());
+    }
+
+    if on_chain < STORAGE_VERSION {
+        STORAGE_VERSION.put::<Pallet<T>>();
+        weight = weight.saturating_add(T::DbWeight::get().writes(1));
+        log::info!("Migrated zeropool storage from {:?} to {:?}", on_chain, STORAGE_VERSION);
+    }
+
+    weight
+}
+
+#[cfg(feature = "try-runtime")]
+pub fn pre_upgrade<T: Config>() -> Result<(), &'static str> {
+    if <Pallet<T>>::on_chain_storage_version() < 1 {
+        v1::pre_upgrade::<T>()?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "try-runtime")]
+pub fn post_upgrade<T: Config>() -> Result<(), &'static str> {
+    if <Pallet<T>>::on_chain_storage_version() != STORAGE_VERSION {
+        return Err("Storage version was not updated")
+    }
+    v1::post_upgrade::<T>()?;
+    v2::post_upgrade::<T>()?;
+    <Pallet<T>>::check_solvency()
+}
+
+/// Verification keys used to be stored as bare Groth16 keys, before they were tagged with their
+/// proof system.
+pub mod v1 {
+    use super::*;
+    use crate::{verifier::Groth16VK, TransferVk, TreeVk, VK};
+
+    pub fn migrate<T: Config>() -> Weight {
+        let mut writes = 0;
+        for (name, result) in [
+            ("transfer", <TransferVk<T>>::translate::<Groth16VK, _>(|old| old.map(VK::Groth16))),
+            ("tree", <TreeVk<T>>::translate::<Groth16VK, _>(|old| old.map(VK::Groth16))),
+        ] {
+            match result {
+                Ok(Some(_)) => writes += 1,
+                Ok(None) => {},
+                Err(_) => log::warn!("Failed to decode the legacy {} verification key", name),
+            }
+        }
+
+        T::DbWeight::get().reads_writes(2, writes)
+    }
+
+    #[cfg(feature = "try-runtime")]
+    pub fn pre_upgrade<T: Config>() -> Result<(), &'static str> {
+        use codec::Decode;
+        use frame_support::storage::unhashed;
+
+        for key in [<TransferVk<T>>::hashed_key(), <TreeVk<T>>::hashed_key()] {
+            if let Some(raw) = unhashed::get_raw(&key) {
+                Groth16VK::decode(&mut &raw[..])
+                    .map_err(|_| "Legacy verification key is not a Groth16 key")?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "try-runtime")]
+    pub fn post_upgrade<T: Config>() -> Result<(), &'static str> {
+        if <TransferVk<T>>::exists() && <TransferVk<T>>::try_get().is_err() {
+            return Err("Transfer verification key does not decode")
+        }
+        if <TreeVk<T>>::exists() && <TreeVk<T>>::try_get().is_err() {
+            return Err("Tree verification key does not decode")
+        }
+        Ok(())
+    }
+}
+
+/// The root history used to grow forever, it is now bounded by `RootHistorySize`. The expired
+/// roots are removed in `on_idle`, a few at a time.
+pub mod v2 {
+    use super::*;
+    use crate::{num::NativeU256, PoolIndex, Roots, RootsToPrune};
+
+    /// Maximum number of roots pruned in a block, whatever the remaining weight.
+    pub const MAX_ROOTS_PRUNED_PER_BLOCK: u64 = 1024;
+
+    fn oldest_root<T: Config>() -> NativeU256 {
+        let pool_index: NativeU256 = <PoolIndex<T>>::get();
+        let span: NativeU256 = <Pallet<T>>::root_history_span().into();
+        pool_index.saturating_sub(span)
+    }
+
+    /// Schedules the removal of the roots that are older than `RootHistorySize` transactions.
+    pub fn migrate<T: Config>() -> Weight {
+        let oldest = oldest_root::<T>();
+        if oldest.is_zero() {
+            return T::DbWeight::get().reads(2)
+        }
+
+        <RootsToPrune<T>>::put((NativeU256::zero(), oldest));
+        log::info!("Scheduled the pruning of the roots older than {}", oldest);
+
+        T::DbWeight::get().reads_writes(2, 1)
+    }
+
+    /// Removes as many of the scheduled roots as fit into `remaining_weight`.
+    pub fn prune_roots<T: Config>(remaining_weight: Weight) -> Weight {
+        let db = T::DbWeight::get();
+        let overhead = db.reads_writes(1, 1);
+        if remaining_weight < overhead {
+            return 0
+        }
+
+        let (mut next, end) = match <RootsToPrune<T>>::get() {
+            Some(range) => range,
+            None => return db.reads(1),
+        };
+
+        let max =
+            ((remaining_weight - overhead) / db.writes(1).max(1)).min(MAX_ROOTS_PRUNED_PER_BLOCK);
+        let mut pruned = 0;
+        while next < end && pruned < max {
+            <Roots<T>>::remove(next);
+            next = next.saturating_add(128u32.into());
+            pruned += 1;
+        }
+
+        if next < end {
+            <RootsToPrune<T>>::put((next, end));
+        } else {
+            <RootsToPrune<T>>::kill();
+            log::info!("Pruned the roots older than {}", end);
+        }
+
+        overhead.saturating_add(db.writes(pruned))
+    }
+
+    #[cfg(feature = "try-runtime")]
+    pub fn post_upgrade<T: Config>() -> Result<(), &'static str> {
+        let oldest = oldest_root::<T>();
+        let scheduled = |index: NativeU256| {
+            <RootsToPrune<T>>::get().map_or(false, |(next, end)| next <= index && index < end)
+        };
+        if <Roots<T>>::iter_keys().any(|index| index < oldest && !scheduled(index)) {
+            return Err("Roots older than the root history are not scheduled for pruning")
+        }
+        Ok(())
+    }
+}
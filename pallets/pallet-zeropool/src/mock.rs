@@ -1,11 +1,11 @@
 use crate::{self as pallet_zeropool, num::U256};
 use ff_uint::Uint;
-use frame_benchmarking::whitelisted_caller;
 use frame_support::{parameter_types, PalletId};
 use sp_core::{crypto::AccountId32, H256};
 use sp_runtime::{
     testing::Header,
     traits::{BlakeTwo256, IdentityLookup},
+    FixedU128,
 };
 
 type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
@@ -13,11 +13,9 @@ type Block = frame_system::mocking::MockBlock<Test>;
 type Balance = u128;
 type AccountId = AccountId32;
 
-// const OWNER: AccountId = AccountId::new(hex_literal::hex!(
-//     "d000ac5048ae858aca2e6aa43e00661562a47026fe88ff83992430204a159752"
-// ));
-
-const OWNER: AccountId = whitelisted_caller();
+pub const OWNER: AccountId = AccountId::new(hex_literal::hex!(
+    "d000ac5048ae858aca2e6aa43e00661562a47026fe88ff83992430204a159752"
+));
 
 // Configure a mock runtime to test the pallet.
 frame_support::construct_runtime!(
@@ -29,6 +27,8 @@ frame_support::construct_runtime!(
         System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
         Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
         Zeropool: pallet_zeropool::{Pallet, Call, Storage, Event<T>},
+        ZeropoolOperatorManager: pallet_zeropool::operator::{Pallet, Call, Storage, Event<T>},
+        ZeropoolCompliance: pallet_zeropool::compliance::{Pallet, Call, Storage, Event<T>},
     }
 );
 
@@ -80,6 +80,15 @@ impl pallet_balances::Config for Test {
 parameter_types! {
     pub const TestPalletId: PalletId = PalletId(*b"zeropool");
     pub const InitialOwner: <Test as frame_system::Config>::AccountId = OWNER;
+    pub const ForcedTxFee: Balance = 50;
+    pub const ForcedTxInclusionPeriod: u64 = 10;
+    pub const MaxForcedTxSize: u32 = 4096;
+    pub const RootHistorySize: u32 = 4;
+    pub const MaxDirectDepositsPerBatch: u32 = 16;
+    pub const DirectDepositTimeout: u64 = 10;
+    pub const RewardAccount: AccountId = AccountId::new([9; 32]);
+    pub const EnergyRewardRate: FixedU128 = FixedU128::from_inner(1_000_000_000_000_000_000);
+    pub const MaxNativeRate: FixedU128 = FixedU128::from_inner(2_000_000_000_000_000_000);
 }
 
 impl pallet_zeropool::Config for Test {
@@ -87,23 +96,74 @@ impl pallet_zeropool::Config for Test {
     type PalletId = TestPalletId;
     type Currency = Balances;
 
-    type OperatorManager = ();
+    type OperatorManager = ZeropoolOperatorManager;
+    type ComplianceFilter = ZeropoolCompliance;
     type InitialOwner = InitialOwner;
+    type PauseOrigin = pallet_zeropool::EnsureOwner<Test>;
+    type AdminOrigin = frame_system::EnsureRoot<AccountId>;
+    type GuardianOrigin = pallet_zeropool::EnsureOwner<Test>;
+    type ForcedTxFee = ForcedTxFee;
+    type ForcedTxInclusionPeriod = ForcedTxInclusionPeriod;
+    type MaxForcedTxSize = MaxForcedTxSize;
+    type RootHistorySize = RootHistorySize;
+    type RewardCurrency = Balances;
+    type RewardAccount = RewardAccount;
+    type EnergyRewardRate = EnergyRewardRate;
+    type MaxNativeRate = MaxNativeRate;
+    type Treasury = ();
+    type WeightInfo = ();
+    type MaxDirectDepositsPerBatch = MaxDirectDepositsPerBatch;
+    type DirectDepositTimeout = DirectDepositTimeout;
+}
+
+parameter_types! {
+    pub const MaxOperators: u32 = 4;
+    pub const MaxMetadataLength: u32 = 128;
+    pub const SlotLength: u64 = 10;
+    pub const OpenModeTimeout: u64 = 5;
+    pub const OperatorBond: Balance = 100;
+    pub const UnbondingDelay: u64 = 10;
+    pub const MissedSlotSlash: Balance = 10;
 }
 
 impl pallet_zeropool::operator::Config for Test {
     type Event = Event;
-    type InitialOwner = InitialOwner;
+    type OwnerOrigin = pallet_zeropool::EnsureOwner<Test>;
+    type MaxOperators = MaxOperators;
+    type MaxMetadataLength = MaxMetadataLength;
+    type SlotLength = SlotLength;
+    type OpenModeTimeout = OpenModeTimeout;
+    type Currency = Balances;
+    type OperatorBond = OperatorBond;
+    type UnbondingDelay = UnbondingDelay;
+    type MissedSlotSlash = MissedSlotSlash;
+    type Slash = ();
+    type SlashOrigin = frame_system::EnsureRoot<AccountId>;
+}
+
+impl pallet_zeropool::compliance::Config for Test {
+    type Event = Event;
+    type AdminOrigin = frame_system::EnsureRoot<AccountId>;
 }
 
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
     let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
-    pallet_balances::GenesisConfig::<Test> { balances: vec![(OWNER, 1000000000000000000)] }
+    let mut balances = vec![(OWNER, 1000000000000000000)];
+    // Operator candidates
+    balances.extend((1..=4).map(|i| (AccountId::new([i; 32]), 1000)));
+    // The pool account is kept alive
+    balances.push((Zeropool::account_id(), ExistentialDeposit::get()));
+    balances.push((RewardAccount::get(), 1000000000000000000));
+    pallet_balances::GenesisConfig::<Test> { balances }
         .assimilate_storage(&mut t)
         .unwrap();
 
     let mut ext = sp_io::TestExternalities::new(t);
-    ext.execute_with(|| System::set_block_number(1));
+    ext.execute_with(|| {
+        System::set_block_number(1);
+        pallet_zeropool::STORAGE_VERSION.put::<Zeropool>();
+        pallet_zeropool::operator::STORAGE_VERSION.put::<ZeropoolOperatorManager>();
+    });
     ext
 }
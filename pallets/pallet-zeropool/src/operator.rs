@@ -1,11 +1,20 @@
-use frame_support::dispatch::DispatchResult;
+use sp_runtime::FixedU128;
 
 pub trait OperatorManager<AccountId>
 where
     AccountId: PartialEq,
 {
+    /// Whether the account is allowed to submit transactions at the current block.
     fn is_operator(account: AccountId) -> bool;
-    fn set_owner(new_owner: AccountId) -> DispatchResult;
+    /// Called by the pool after a transaction submitted by `account` has been processed.
+    /// `forced` is set for a transaction from the forced queue, which anyone can include.
+    fn note_transaction(account: AccountId, forced: bool);
+    /// Rate at which `account` sends native tokens to withdrawing users, in pool units per
+    /// native unit. `None` if the operator doesn't offer native top-ups.
+    fn native_rate(account: AccountId) -> Option<FixedU128>;
+    /// Minimum fee `account` accepts for a transaction with a memo of `memo_size` bytes, in
+    /// pool units.
+    fn min_fee(account: AccountId, memo_size: u32) -> u64;
 }
 
 impl<AccountId: PartialEq> OperatorManager<AccountId> for () {
@@ -13,8 +22,14 @@ impl<AccountId: PartialEq> OperatorManager<AccountId> for () {
         true
     }
 
-    fn set_owner(_new_owner: AccountId) -> DispatchResult {
-        Ok(())
+    fn note_transaction(_account: AccountId, _forced: bool) {}
+
+    fn native_rate(_account: AccountId) -> Option<FixedU128> {
+        None
+    }
+
+    fn min_fee(_account: AccountId, _memo_size: u32) -> u64 {
+        0
     }
 }
 
@@ -23,73 +38,416 @@ pub use pallet::*;
 /// A simple implementation of OperatorManager pallet.
 #[frame_support::pallet]
 pub mod pallet {
-    use super::OperatorManager;
-    use frame_support::pallet_prelude::*;
+    use super::{FixedU128, OperatorManager};
+    use frame_support::{
+        pallet_prelude::*,
+        storage::migration,
+        traits::{Currency, OnUnbalanced, PalletInfoAccess, ReservableCurrency},
+        CloneNoBound, DefaultNoBound, EqNoBound, PartialEqNoBound, RuntimeDebugNoBound,
+    };
     use frame_system::pallet_prelude::*;
+    use sp_runtime::traits::{SaturatedConversion, Saturating, Zero};
+
+    pub type BalanceOf<T> =
+        <<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+    pub type NegativeImbalanceOf<T> = <<T as Config>::Currency as Currency<
+        <T as frame_system::Config>::AccountId,
+    >>::NegativeImbalance;
+
+    /// Bond of a removed operator that can be withdrawn once `unlock_at` is reached.
+    #[derive(Clone, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug)]
+    pub struct Unbonding<Balance, BlockNumber> {
+        pub amount: Balance,
+        pub unlock_at: BlockNumber,
+    }
+
+    pub type UnbondingOf<T> = Unbonding<BalanceOf<T>, <T as frame_system::Config>::BlockNumber>;
+
+    /// Fees charged by an operator, in pool units.
+    #[derive(
+        Clone, Copy, Default, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo, RuntimeDebug,
+    )]
+    pub struct FeeSchedule {
+        pub base_fee: u64,
+        pub per_byte_fee: u64,
+    }
+
+    impl FeeSchedule {
+        /// Returns the fee of a transaction with a memo of `memo_size` bytes.
+        pub fn fee(&self, memo_size: u32) -> u64 {
+            self.base_fee.saturating_add(self.per_byte_fee.saturating_mul(memo_size.into()))
+        }
+    }
+
+    /// Public metadata of an operator, used by clients to discover relayers.
+    #[derive(
+        CloneNoBound,
+        DefaultNoBound,
+        PartialEqNoBound,
+        EqNoBound,
+        Encode,
+        Decode,
+        MaxEncodedLen,
+        TypeInfo,
+        RuntimeDebugNoBound,
+    )]
+    #[codec(mel_bound())]
+    #[scale_info(skip_type_params(MaxLength))]
+    pub struct OperatorInfo<MaxLength: Get<u32>> {
+        pub name: BoundedVec<u8, MaxLength>,
+        pub relayer_url: BoundedVec<u8, MaxLength>,
+        pub fee: FeeSchedule,
+    }
+
+    pub type OperatorInfoOf<T> = OperatorInfo<<T as Config>::MaxMetadataLength>;
 
     #[pallet::config]
     pub trait Config: frame_system::Config {
         type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
 
+        /// Origin that manages the operator set, e.g. `pallet_zeropool::EnsureOwner` to share
+        /// the owner of the pool.
+        type OwnerOrigin: EnsureOrigin<Self::Origin>;
+
+        /// Maximum number of registered operators.
         #[pallet::constant]
-        type InitialOwner: Get<Self::AccountId>;
+        type MaxOperators: Get<u32>;
+
+        /// Maximum length of the operator name and relayer URL.
+        #[pallet::constant]
+        type MaxMetadataLength: Get<u32>;
+
+        /// Number of blocks in a slot. Slots are assigned to the operators in round-robin order.
+        #[pallet::constant]
+        type SlotLength: Get<Self::BlockNumber>;
+
+        /// If the slot owner hasn't included a transaction for this many blocks, anyone can
+        /// submit transactions until the slot owner does so again or the slot ends.
+        #[pallet::constant]
+        type OpenModeTimeout: Get<Self::BlockNumber>;
+
+        type Currency: ReservableCurrency<Self::AccountId>;
+
+        /// Amount reserved from an operator's account on registration.
+        #[pallet::constant]
+        type OperatorBond: Get<BalanceOf<Self>>;
+
+        /// Number of blocks the bond stays reserved (and slashable) after the operator is removed.
+        #[pallet::constant]
+        type UnbondingDelay: Get<Self::BlockNumber>;
+
+        /// Amount slashed from the slot owner when someone else has to submit a transaction
+        /// because the owner hasn't included any during its slot for `OpenModeTimeout` blocks.
+        /// Applied at most once per slot, forced transactions don't count.
+        #[pallet::constant]
+        type MissedSlotSlash: Get<BalanceOf<Self>>;
+
+        /// Handler for the slashed funds, e.g. a treasury.
+        type Slash: OnUnbalanced<NegativeImbalanceOf<Self>>;
+
+        /// Origin allowed to slash operators.
+        type SlashOrigin: EnsureOrigin<Self::Origin>;
     }
 
+    /// Version 1 replaced the single `Operator` and the `Owner` with the operator registry.
+    pub const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+
     #[pallet::pallet]
     #[pallet::generate_store(pub(super) trait Store)]
+    #[pallet::storage_version(STORAGE_VERSION)]
     pub struct Pallet<T>(_);
 
+    /// Registered operators in the order they were added.
     #[pallet::storage]
-    pub type Operator<T: Config> = StorageValue<_, T::AccountId>;
+    pub type OperatorList<T: Config> =
+        StorageValue<_, BoundedVec<T::AccountId, T::MaxOperators>, ValueQuery>;
 
-    #[pallet::type_value]
-    pub fn DefaultOwner<T: Config>() -> T::AccountId {
-        T::InitialOwner::get()
-    }
+    #[pallet::storage]
+    pub type Operators<T: Config> =
+        StorageMap<_, Blake2_128Concat, T::AccountId, OperatorInfoOf<T>>;
+
+    /// Last block at which the slot owner included a transaction.
+    #[pallet::storage]
+    pub type LastTransaction<T: Config> = StorageValue<_, T::BlockNumber, ValueQuery>;
+
+    /// Start of the last slot whose owner was slashed for missing it.
+    #[pallet::storage]
+    pub type LastSlashedSlot<T: Config> = StorageValue<_, T::BlockNumber>;
+
+    /// Bonds of the registered operators.
+    #[pallet::storage]
+    pub type Bonds<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, BalanceOf<T>>;
+
+    /// Bonds of the removed operators awaiting withdrawal.
+    #[pallet::storage]
+    pub type Unbondings<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, UnbondingOf<T>>;
 
+    /// Rates at which the operators send native tokens to withdrawing users, in pool units per
+    /// native unit.
     #[pallet::storage]
-    pub type Owner<T: Config> = StorageValue<_, T::AccountId, ValueQuery, DefaultOwner<T>>;
+    pub type NativeRates<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, FixedU128>;
 
     #[pallet::event]
     #[pallet::generate_deposit(pub(super) fn deposit_event)]
     pub enum Event<T: Config> {
         /// [who]
-        OperatorChanged(T::AccountId),
+        OperatorAdded(T::AccountId),
+        /// [who]
+        OperatorRemoved(T::AccountId),
         /// [who]
-        OwnerChanged(T::AccountId),
+        OperatorInfoUpdated(T::AccountId),
+        /// [who, amount]
+        Bonded(T::AccountId, BalanceOf<T>),
+        /// [who, amount, unlock_at]
+        Unbonded(T::AccountId, BalanceOf<T>, T::BlockNumber),
+        /// [who, amount]
+        BondWithdrawn(T::AccountId, BalanceOf<T>),
+        /// [who, amount]
+        Slashed(T::AccountId, BalanceOf<T>),
+        /// [who, rate]
+        NativeRateUpdated(T::AccountId, Option<FixedU128>),
     }
 
     #[pallet::error]
     pub enum Error<T> {
         NotOwner,
+        NotOperator,
+        AlreadyOperator,
+        TooManyOperators,
+        InsufficientBond,
+        NotUnbonding,
+        BondLocked,
+    }
+
+    #[pallet::hooks]
+    impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+        fn on_runtime_upgrade() -> Weight {
+            if Self::on_chain_storage_version() >= STORAGE_VERSION {
+                return T::DbWeight::get().reads(1)
+            }
+
+            // Moves the legacy single operator into the registry, the owner is replaced by
+            // `Config::OwnerOrigin`. An operator that can't pay the bond has to register again.
+            let pallet = <Self as PalletInfoAccess>::name().as_bytes();
+            migration::take_storage_value::<T::AccountId>(pallet, b"Owner", &[]);
+            if let Some(operator) =
+                migration::take_storage_value::<T::AccountId>(pallet, b"Operator", &[])
+            {
+                let bond = T::OperatorBond::get();
+                if T::Currency::reserve(&operator, bond).is_ok() {
+                    let _ = Self::insert_operator(operator.clone(), Default::default());
+                    <Bonds<T>>::insert(&operator, bond);
+
+                    Self::deposit_event(Event::Bonded(operator, bond));
+                } else {
+                    log::warn!("The legacy operator can't pay the bond and is not registered");
+                }
+            }
+
+            STORAGE_VERSION.put::<Self>();
+
+            T::DbWeight::get().reads_writes(6, 8)
+        }
     }
 
     impl<T: Config> Pallet<T> {
-        fn owner() -> T::AccountId {
-            <Owner<T>>::get()
+        /// Returns the owner of the current slot and the block at which the slot started.
+        pub fn current_slot() -> Option<(T::AccountId, T::BlockNumber)> {
+            let operators = <OperatorList<T>>::get();
+            if operators.is_empty() {
+                return None
+            }
+
+            let now = <frame_system::Pallet<T>>::block_number();
+            let slot_length = T::SlotLength::get().max(1u32.into());
+            let slot = now / slot_length;
+            let index = (slot % (operators.len() as u32).into()).saturated_into::<usize>();
+
+            Some((operators[index].clone(), slot * slot_length))
         }
 
-        fn check_owner(origin: OriginFor<T>) -> Result<T::AccountId, DispatchError> {
-            let who = ensure_signed(origin)?;
-            let owner = Self::owner();
+        /// Whether the pool is open for transactions from any account.
+        pub fn is_open_mode() -> bool {
+            match Self::current_slot() {
+                Some((_, slot_start)) => {
+                    let now = <frame_system::Pallet<T>>::block_number();
+                    let idle_since = slot_start.max(<LastTransaction<T>>::get());
+                    now >= idle_since.saturating_add(T::OpenModeTimeout::get())
+                },
+                None => false,
+            }
+        }
+
+        /// Slashes up to `amount` from the bond of `who`, taking it from the active bond first
+        /// and then from the unbonding one. Returns the slashed amount.
+        pub fn slash(who: &T::AccountId, amount: BalanceOf<T>) -> BalanceOf<T> {
+            let mut remaining = amount;
+
+            <Bonds<T>>::mutate(who, |bond| {
+                if let Some(bond) = bond {
+                    let slashed = remaining.min(*bond);
+                    *bond -= slashed;
+                    remaining -= slashed;
+                }
+            });
+            <Unbondings<T>>::mutate(who, |unbonding| {
+                if let Some(unbonding) = unbonding {
+                    let slashed = remaining.min(unbonding.amount);
+                    unbonding.amount -= slashed;
+                    remaining -= slashed;
+                }
+            });
+
+            let slashed = amount - remaining;
+            if !slashed.is_zero() {
+                let (imbalance, _) = T::Currency::slash_reserved(who, slashed);
+                T::Slash::on_unbalanced(imbalance);
 
-            if who != owner {
-                return Err(Error::<T>::NotOwner.into())
+                Self::deposit_event(Event::Slashed(who.clone(), slashed));
             }
 
-            Ok(who)
+            slashed
+        }
+
+        fn check_owner(origin: OriginFor<T>) -> DispatchResult {
+            T::OwnerOrigin::ensure_origin(origin).map_err(|_| Error::<T>::NotOwner)?;
+
+            Ok(())
+        }
+
+        fn insert_operator(address: T::AccountId, info: OperatorInfoOf<T>) -> DispatchResult {
+            if <Operators<T>>::contains_key(&address) {
+                return Err(Error::<T>::AlreadyOperator.into())
+            }
+
+            <OperatorList<T>>::try_mutate(|list| list.try_push(address.clone()))
+                .map_err(|_| Error::<T>::TooManyOperators)?;
+            <Operators<T>>::insert(&address, info);
+
+            Self::deposit_event(Event::OperatorAdded(address));
+
+            Ok(())
         }
     }
 
     #[pallet::call]
     impl<T: Config> Pallet<T> {
-        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1, 1))]
-        pub fn set_operator(origin: OriginFor<T>, address: T::AccountId) -> DispatchResult {
+        /// Registers an operator, reserving `OperatorBond` from its account.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(5, 4))]
+        pub fn add_operator(
+            origin: OriginFor<T>,
+            address: T::AccountId,
+            info: OperatorInfoOf<T>,
+        ) -> DispatchResult {
+            Self::check_owner(origin)?;
+
+            let bond = T::OperatorBond::get();
+            if <Operators<T>>::contains_key(&address) {
+                return Err(Error::<T>::AlreadyOperator.into())
+            }
+            T::Currency::reserve(&address, bond).map_err(|_| Error::<T>::InsufficientBond)?;
+
+            Self::insert_operator(address.clone(), info)?;
+            <Bonds<T>>::insert(&address, bond);
+
+            Self::deposit_event(Event::Bonded(address, bond));
+
+            Ok(())
+        }
+
+        /// Removes an operator. Its bond stays reserved for `UnbondingDelay` blocks.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(4, 4))]
+        pub fn remove_operator(origin: OriginFor<T>, address: T::AccountId) -> DispatchResult {
             Self::check_owner(origin)?;
 
-            <Operator<T>>::put(address.clone());
+            if <Operators<T>>::take(&address).is_none() {
+                return Err(Error::<T>::NotOperator.into())
+            }
+            <OperatorList<T>>::mutate(|list| list.retain(|op| op != &address));
+            <NativeRates<T>>::remove(&address);
+
+            if let Some(bond) = <Bonds<T>>::take(&address) {
+                let unlock_at = <frame_system::Pallet<T>>::block_number()
+                    .saturating_add(T::UnbondingDelay::get());
+                let amount = <Unbondings<T>>::get(&address)
+                    .map_or(bond, |unbonding| unbonding.amount.saturating_add(bond));
+                <Unbondings<T>>::insert(&address, Unbonding { amount, unlock_at });
+
+                Self::deposit_event(Event::Unbonded(address.clone(), bond, unlock_at));
+            }
+
+            Self::deposit_event(Event::OperatorRemoved(address));
+
+            Ok(())
+        }
+
+        /// Releases the bond of a removed operator once the unbonding delay has passed.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(2, 2))]
+        pub fn withdraw_unbonded(origin: OriginFor<T>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            let unbonding = <Unbondings<T>>::get(&who).ok_or(Error::<T>::NotUnbonding)?;
+            if <frame_system::Pallet<T>>::block_number() < unbonding.unlock_at {
+                return Err(Error::<T>::BondLocked.into())
+            }
+
+            <Unbondings<T>>::remove(&who);
+            T::Currency::unreserve(&who, unbonding.amount);
+
+            Self::deposit_event(Event::BondWithdrawn(who, unbonding.amount));
+
+            Ok(())
+        }
+
+        /// Slashes part of an operator's bond, including a bond that is still unbonding.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(3, 3))]
+        pub fn slash_operator(
+            origin: OriginFor<T>,
+            address: T::AccountId,
+            amount: BalanceOf<T>,
+        ) -> DispatchResult {
+            T::SlashOrigin::ensure_origin(origin)?;
+
+            Self::slash(&address, amount);
+
+            Ok(())
+        }
+
+        /// Updates the metadata of the calling operator.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1, 1))]
+        pub fn update_operator_info(
+            origin: OriginFor<T>,
+            info: OperatorInfoOf<T>,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            <Operators<T>>::try_mutate(&who, |current| -> DispatchResult {
+                let current = current.as_mut().ok_or(Error::<T>::NotOperator)?;
+                *current = info;
+                Ok(())
+            })?;
+
+            Self::deposit_event(Event::OperatorInfoUpdated(who));
+
+            Ok(())
+        }
+
+        /// Publishes the rate at which the calling operator sends native tokens to withdrawing
+        /// users, or stops the top-ups if `rate` is `None`.
+        #[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1, 1))]
+        pub fn set_native_rate(origin: OriginFor<T>, rate: Option<FixedU128>) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            if !<Operators<T>>::contains_key(&who) {
+                return Err(Error::<T>::NotOperator.into())
+            }
 
-            Self::deposit_event(Event::OperatorChanged(address));
+            match rate {
+                Some(rate) => <NativeRates<T>>::insert(&who, rate),
+                None => <NativeRates<T>>::remove(&who),
+            }
+
+            Self::deposit_event(Event::NativeRateUpdated(who, rate));
 
             Ok(())
         }
@@ -97,15 +455,45 @@ pub mod pallet {
 
     impl<T: Config> OperatorManager<T::AccountId> for Pallet<T> {
         fn is_operator(account: T::AccountId) -> bool {
-            Operator::<T>::get().map(|op| op == account).unwrap_or(false)
+            match Self::current_slot() {
+                Some((slot_owner, _)) => slot_owner == account || Self::is_open_mode(),
+                None => false,
+            }
         }
 
-        fn set_owner(new_owner: T::AccountId) -> DispatchResult {
-            <Owner<T>>::put(new_owner.clone());
+        fn note_transaction(account: T::AccountId, forced: bool) {
+            let (slot_owner, slot_start) = match Self::current_slot() {
+                Some(slot) => slot,
+                None => return,
+            };
+
+            let now = <frame_system::Pallet<T>>::block_number();
+            if slot_owner == account {
+                <LastTransaction<T>>::put(now);
+                return
+            }
 
-            Self::deposit_event(Event::<T>::OwnerChanged(new_owner));
+            // Overdue forced transactions can be drained by anyone, in any slot
+            if forced {
+                return
+            }
 
-            Ok(())
+            // Someone else had to step in while the slot owner hadn't been active in its slot
+            let missed = <LastTransaction<T>>::get() < slot_start &&
+                now >= slot_start.saturating_add(T::OpenModeTimeout::get());
+            let penalty = T::MissedSlotSlash::get();
+            if missed && !penalty.is_zero() && <LastSlashedSlot<T>>::get() != Some(slot_start) {
+                <LastSlashedSlot<T>>::put(slot_start);
+                Self::slash(&slot_owner, penalty);
+            }
+        }
+
+        fn native_rate(account: T::AccountId) -> Option<FixedU128> {
+            <NativeRates<T>>::get(account)
+        }
+
+        fn min_fee(account: T::AccountId, memo_size: u32) -> u64 {
+            <Operators<T>>::get(account).map_or(0, |info| info.fee.fee(memo_size))
         }
     }
 }
@@ -1,22 +1,30 @@
-use crate::mock::*;
-use frame_support::{assert_err, assert_ok};
-use hex_literal::hex;
-use sp_core::crypto::AccountId32;
-
-// Prebuilt transactions for the testing mnemonic (test * 11 junk)
-
-// deposit 3000000000
-// 0x00000000281879554ace64fedf59ee5fd2c57b6d3a2b86a3ca0a263615c10a729a17d76018e818c5e0bf508504f702688537fbd0aefc2501aa16420564914118f6930e25000000000000000000000000000000000000000000000000002dc6c010d64987a633c0a7024142432a63869d8b55e1854a84fce07b42dd5d5ecb212e2bf800d95099fb334d7287a6a14d680f089dcbcca80fb60bfbf54c29892faadc1072aa5bf487f6edc89693ad55b96494256ff17fd72d301c6f63273a0c1ac97427b79744dd18830eb7b82aa757bf51655c42d921b39ec3f9253452886b3fc6811c642f67b3101e70820bcbe959aa2cbd7e98c24110f9da2e20c457b19e74f031269a8748132f4b54d5a969dad6caaeca880f91b43bf3df9b93584926e222a14f24c53d543f24ef4f2aee94a1ee65a9db2961d00eb3e9ca3bba86954d95a64ff5294d3907ee89b49052e5c7dd9f201f1564d54f48e0675a51be85a29108010b451f73faf276e945be7e2d8b7a63dfd502701cda709b5614f5e748b899baf4645c0f576e761adb25689474e5a482f6d0287d543f3a0e63eb21650c3c5ac6eeaa2717f8661d4725098b4dfb31a5605a479801885421dbb83e632d5117e27f0e06b9186729a664e9b1e16cedf52d10b9360e1ec0f9b9671d32333b03e9f11f696ef516173a857cfb5becffab6e6f2e25f1c30301fc9e06c739e8ea359f39c926a8660b116bbe0c89fa5d6d57d68626acea95710ffd2e6ca10bd4f61e44520d4ec54e263fd4a40d608afa1e258dfdc9e11b3572f61609f8cd33ccb0ad99d48192816e0af394205b48af69bfb700d815334def2f382745bc576e8c311a5aa8b92b44732f2711d6aeb76ab5548ca258b726eb9e9cd169be6ddb0d192a5d415f80746133000000d2000000000000000001000000fc7b3e3cc3d8b3026a04d6a239e65cf636b943458b0ad73563ab198633f3b12825e6cab496554fe315a85be40b6d26311620785110394b25de1c92a7ab3b02168ff62cf8d8bac650d8270cf6824806e0bd744b3e3fcc1b0eef8fb4628175eb473af6de862fc2c4b6ba07487d692539cb836109675a89601817b8c78474e2b7b7eb6a35f07592935ba71b923292b8f6b65aa4647b74305bff97073dc0ec2224fe4f6fcc1cd3faa048459d253ae9935b89939516c28ed11b966baa667619cfdcc2d5688eb742ead000ac5048ae858aca2e6aa43e00661562a47026fe88ff83992430204a159752c8335b630922a0c9dcfa6e4897abecafa3fa8dcd6bb624ef741e843522d34c4a3d547f4a26577c7300f955aeea36d65a9486b3e574f60d8203ee83a9073b288b
-
-// transfer 1000000000 to self
-// 0x00000000237d9ab8ca165b7728b5353ee84423ee3ee44a71c535894a08dc2c476be4c321179204c43f1f98e74eab0c9e668c6e63bdb16907443a977e323dd3023602fb6e000000000080000000000000000000000000000000000000000000002d10fca86c5f882296870b3cee750134cc5af7fd10b5df5fe0c7b1a465b478b829e384af859d777e7ebf339ae7c21bc45bbe2da588d9af5f4f1b556f9a3263232af3fab5aa4f0df62236ecb7938482c862a32207aa397aa0ba0fae47841802e111af294acf087e6cdb385c944eb997a18154fbea9c3f4c34b98517d062ce664321c65349a1070a08a6824a195ca4ccc8f3a4ee73f4d55ce19ec626513b5684772df66f128fbfeadb4c43afa8b0133c0c98872d2f07fbd5d9abdaf6a7434ee227112747c3e3ab974996366e183cbba0dc98cbdb50b6f8f68832924695475a49c908123f64dde854f360a3a5ff30fe0802d9b26cf931f3d1356527efc9f240cc0229099ed53518664aa52c9cdeb77ebbea7616fa25506073d8d29162e5c73b916105d6c07495bdd93eb60a3dff79771a88baba6e7ad3f4bb4855e18bba78226f2b0f2db408d145faf5835fd11d6a12a18e159ffc4869b398849239259cd5f6b0a61d86ec7af5dad43bfccb9d20d4a9dc0b004760c8b7683bf3147783b1d4d2603c19a0829f443e907797acbda88bd31f67646b509ff2636fa554b4e30d95e3fff527f9cbce3cbaa8a58ad74ea2d1d549e2cfa2b8cd46a7ded72c39e6b752cf3a2f271fdb02f7e34d938bd5fe16d60786faa1addb87eabc153ad161eeaccf247d8e2a5aac24078162d70963760ca6221d35b00a41f9fe6643facb8ddac452a4fc8402dfa64efc00b497fc81884cc4820060b3fb809852d8445493c256ade00024070001017e0000000000000000020000002dedb8d83de8fd17b97a0c511bd8dfcbbf5a857c34c60c7b429d4dbf811aae28f627302e3a6384d3b7b97d571e9ccb8651f2405febfd9596bece416900684417730f108b1f1edba449c5a75cc27e4cf8486ea2a883561b1667ea1cd338eff42fae7b03ffdbafc43c8f2f3cea96cef62584bcda71768439882b78926602d20ac3523734ad517e701319d3984967ff6601760a726ef1d6b18476338ba321b0fd88088062ba8b8b78c751863c574a9618f4598c43d314f290bb3ee40a11a632600c34871f184450c7306e6904f4e737505043aed92bfa356689ce706a70d7ce8ac80d65a0a2877e36e980aad643b6423ae12ef414f665c018de9e15d0f03b9e3351cd55a134e8c0e8bd1cf8a37126f31f50f8a492d6f1c737bf81e1af3f98b300691c76983003053091f2007a0dce6bfa90c0219d78ce02ea0e05299565f5a9e2a4cf1347dc3ac33488f165f04f0171ca0070fd1fe19aeec1ce1ecbf306167cd09151f181b4be4c1e7e93bb4f430863333eab18
-
-// withdraw 3000000000
-// 0x000000002c985dbf440ba5d71866be3aab012bc487f32daf57f912c9efe3badd920c63db07b95d4d84ff468f72c226f8e0cec59e6f8d0b2af55a4a99f0c4335df79308840000000001000000000000000000000000000000ffffffffffd239401e76605d181bf056f5db4aeea7ab11f6f998cea56427fee2e0ab17e5f0593a701df6a9fec0f64939ddf60bc3a1a06a161074f30b97ffcdca95fb2082affae1ed08b0b51ccef92b2d29117c26f8471dc86db8f650b995eaeec4648ddded95a8650297b462124bc86c3f6875ebfd7c95860a7e6cb12041ca8eeb434f5c96f296b601548f068ce0d1d14e6a00f3866a32083bdce217480705c336a177ecac8295b712bbf5b47fb2475672c19e2f79429b668a815c18dfdbf569bc3031a8b97038a61a87c42985b6fad4c846320f8a02e0bf8defb9adfb4ba61213c32969cd8f0bad0690a04fdc795a4fa481784d39dd9b8e5e65db3247c3039a8b6864416cd562bb1216a80df8beb5b525f72c5d33640d4eadd0fe25faff6ab9d4302e1866876e7b0012b14735565d70674622aa29e7a16f4abf5c263c6f013eabb74955b66f12a4130ddda82bd8054d0768290629178f041cb97473fea93f9af3fc1af9b4c4667721c426b74da384c7c305b3a1db26687dc4f1ff1e914bb7b6aed2681c50e762a41dfe9e0e28c3e6625fcdb4cecd2b180d584214c5d3bb810890eba26ec3daf6c5142fea4c03b570a5690199cd71b0d1b3ccdcdef666ed9ddf6ada0b8803568aaa12af788940c776ed416e8f8171efbca512e835bc7d21bde98392436209657acc2015cc37828dc572a319e1ab69c0375281dc9834b604e04b07b796bbfa14418b09b3a8f848d37fc8a341b31a285945fdb6a33534fa76e15dffd65dd26728cec6000200fa00000000000000000000000000000000d000ac5048ae858aca2e6aa43e00661562a47026fe88ff83992430204a1597520100000002e3e0d869ad18376d317b73d4d56aeca73ec5eb2b8b43216a920e79403fae0783621edf3a8435f9be5acad4a0d945007be6b6131a6fe40333dea8f479511f02d56165f76d4fb9fc8a893e96744cab0801af7bc884afb4c9a20da401f7520ba10d68c950a097a96b3722bad97cc4d51998d02188d2fd74915e82e856559a03c0bc2f24a38ff500254f70923a5ffd8ce7843da20330db7057283de00ad2c663bc449b2d4d3e64dd66d22a42b8aaeddfae65a5b7714b069ca62ab37fac682a26437eaf4f81daa8
-
-const OWNER: AccountId32 = AccountId32::new(hex_literal::hex!(
-    "d000ac5048ae858aca2e6aa43e00661562a47026fe88ff83992430204a159752"
-));
+use crate::{
+    fixtures::{
+        BATCH_PROOF, BATCH_TREE_PROOF, BATCH_TREE_VK, DEPOSIT, DIRECT_DEPOSIT_VK, TRANSFER,
+        WITHDRAW,
+    },
+    mock::*,
+    operator::{self, Bonds, OperatorInfo, OperatorList, Operators},
+    tx_decoder::{TxDecoder, TxType},
+    verifier::{Groth16VK, VK},
+    Commitments, DirectDeposit, DirectDepositHead, DirectDeposits, Error, FeePolicy, ForcedTxHead,
+    ForcedTxs, Limits, NextDirectDepositId, OperatorManager, Owner, Paused, PendingEnergyRewards,
+    PendingOwner, PendingWithdrawal, PendingWithdrawals, PoolIndex, PoolLimits, ProtocolFee,
+    ProtocolFees, Roots, RootsToPrune, ShieldedSupply, TotalDeposited, TotalEnergyRewards,
+    TotalEnergyWithdrawn, TotalFees, TotalProtocolFees, TotalWithdrawn, TransferVk, TreeVk,
+    TxKinds, WindowUsage, WithdrawWindow, WithdrawalDelay, ZkAddress,
+};
+use frame_support::{
+    assert_err, assert_noop, assert_ok,
+    storage::{migration, unhashed},
+    traits::{
+        Currency, Get, GetStorageVersion, Hooks, PalletInfoAccess, ReservableCurrency,
+        StorageVersion,
+    },
+    weights::{Pays, Weight},
+};
+use sp_core::{crypto::AccountId32, U256};
+use sp_runtime::{traits::BadOrigin, FixedPointNumber, FixedU128, Permill};
 
 fn owner() -> Origin {
     Origin::signed(OWNER)
@@ -31,7 +39,7 @@ fn init_state() {
         .expect("download and place the params directory into the js directory");
     assert_ok!(Zeropool::set_tree_vk(owner(), tree_vk));
 
-    assert_ok!(ZeropoolOperatorManager::set_operator(owner(), OWNER));
+    assert_ok!(ZeropoolOperatorManager::add_operator(owner(), OWNER, Default::default()));
 }
 
 #[test]
@@ -39,13 +47,1031 @@ fn test_transact_full_cycle() {
     new_test_ext().execute_with(|| {
         init_state();
 
-        let deposit = hex!["00000000281879554ace64fedf59ee5fd2c57b6d3a2b86a3ca0a263615c10a729a17d76018e818c5e0bf508504f702688537fbd0aefc2501aa16420564914118f6930e25000000000000000000000000000000000000000000000000002dc6c010d64987a633c0a7024142432a63869d8b55e1854a84fce07b42dd5d5ecb212e2bf800d95099fb334d7287a6a14d680f089dcbcca80fb60bfbf54c29892faadc1072aa5bf487f6edc89693ad55b96494256ff17fd72d301c6f63273a0c1ac97427b79744dd18830eb7b82aa757bf51655c42d921b39ec3f9253452886b3fc6811c642f67b3101e70820bcbe959aa2cbd7e98c24110f9da2e20c457b19e74f031269a8748132f4b54d5a969dad6caaeca880f91b43bf3df9b93584926e222a14f24c53d543f24ef4f2aee94a1ee65a9db2961d00eb3e9ca3bba86954d95a64ff5294d3907ee89b49052e5c7dd9f201f1564d54f48e0675a51be85a29108010b451f73faf276e945be7e2d8b7a63dfd502701cda709b5614f5e748b899baf4645c0f576e761adb25689474e5a482f6d0287d543f3a0e63eb21650c3c5ac6eeaa2717f8661d4725098b4dfb31a5605a479801885421dbb83e632d5117e27f0e06b9186729a664e9b1e16cedf52d10b9360e1ec0f9b9671d32333b03e9f11f696ef516173a857cfb5becffab6e6f2e25f1c30301fc9e06c739e8ea359f39c926a8660b116bbe0c89fa5d6d57d68626acea95710ffd2e6ca10bd4f61e44520d4ec54e263fd4a40d608afa1e258dfdc9e11b3572f61609f8cd33ccb0ad99d48192816e0af394205b48af69bfb700d815334def2f382745bc576e8c311a5aa8b92b44732f2711d6aeb76ab5548ca258b726eb9e9cd169be6ddb0d192a5d415f80746133000000d2000000000000000001000000fc7b3e3cc3d8b3026a04d6a239e65cf636b943458b0ad73563ab198633f3b12825e6cab496554fe315a85be40b6d26311620785110394b25de1c92a7ab3b02168ff62cf8d8bac650d8270cf6824806e0bd744b3e3fcc1b0eef8fb4628175eb473af6de862fc2c4b6ba07487d692539cb836109675a89601817b8c78474e2b7b7eb6a35f07592935ba71b923292b8f6b65aa4647b74305bff97073dc0ec2224fe4f6fcc1cd3faa048459d253ae9935b89939516c28ed11b966baa667619cfdcc2d5688eb742ead000ac5048ae858aca2e6aa43e00661562a47026fe88ff83992430204a159752c8335b630922a0c9dcfa6e4897abecafa3fa8dcd6bb624ef741e843522d34c4a3d547f4a26577c7300f955aeea36d65a9486b3e574f60d8203ee83a9073b288b"].into();
-        assert_ok!(Zeropool::transact(owner(), deposit));
+        let info = Zeropool::transact(owner(), DEPOSIT.to_vec()).unwrap();
+        assert_eq!(info.pays_fee, Pays::No);
+        assert_eq!(
+            info.actual_weight,
+            Some(Zeropool::transact_weight(
+                Some(TxType::Deposit),
+                TxDecoder::new(DEPOSIT).memo_size()
+            ))
+        );
+
+        assert_ok!(Zeropool::transact(owner(), TRANSFER.to_vec()));
+
+        assert_ok!(Zeropool::transact(owner(), WITHDRAW.to_vec()));
+
+        assert!(TotalDeposited::<Test>::get() > U256::zero());
+        assert!(TotalWithdrawn::<Test>::get() > U256::zero());
+        assert_eq!(
+            ShieldedSupply::<Test>::get(),
+            TotalDeposited::<Test>::get() -
+                TotalWithdrawn::<Test>::get() -
+                TotalFees::<Test>::get()
+        );
+        assert_ok!(Zeropool::check_solvency());
+    });
+}
+
+#[test]
+fn test_pause() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(ZeropoolOperatorManager::add_operator(owner(), OWNER, Default::default()));
+        let kinds = TxKinds { deposit: true, transfer: false, withdraw: false };
+
+        assert_noop!(Zeropool::pause(Origin::signed(AccountId32::new([1; 32])), kinds), BadOrigin);
+        assert_ok!(Zeropool::pause(owner(), kinds));
+        assert_eq!(Paused::<Test>::get(), kinds);
+
+        assert_err!(Zeropool::transact(owner(), DEPOSIT.to_vec()), Error::<Test>::Paused);
+        // Transfers are not paused and fail further down the line.
+        assert_err!(
+            Zeropool::transact(owner(), TRANSFER.to_vec()),
+            Error::<Test>::TransferVkNotSet
+        );
+
+        // Only the admin origin can unpause.
+        assert_noop!(Zeropool::unpause(owner(), kinds), BadOrigin);
+        assert_ok!(Zeropool::unpause(Origin::root(), kinds));
+        assert_eq!(Paused::<Test>::get(), TxKinds::default());
+    });
+}
+
+#[test]
+fn test_set_limits() {
+    new_test_ext().execute_with(|| {
+        let limits = PoolLimits {
+            tvl_cap: Some(1_000_000),
+            max_tx_amount: Some(1_000),
+            withdraw_window: 100,
+            window_limit: Some(10_000),
+            account_window_limit: None,
+        };
+
+        assert_noop!(Zeropool::set_limits(owner(), limits.clone()), BadOrigin);
+        assert_ok!(Zeropool::set_limits(Origin::root(), limits.clone()));
+        assert_eq!(Limits::<Test>::get(), limits);
+    });
+}
+
+#[test]
+fn test_deposit_limits() {
+    new_test_ext().execute_with(|| {
+        let sender = AccountId32::new([6; 32]);
+        let receiver = ZkAddress { diversifier: [1; 10], pk: [2; 32] };
+        Balances::make_free_balance_be(&sender, 10_000);
+
+        let limits = PoolLimits {
+            tvl_cap: Some(ExistentialDeposit::get() + 3_000),
+            max_tx_amount: Some(2_000),
+            ..Default::default()
+        };
+        assert_ok!(Zeropool::set_limits(Origin::root(), limits));
+
+        assert_noop!(
+            Zeropool::direct_deposit(Origin::signed(sender.clone()), receiver, 3_000),
+            Error::<Test>::TxAmountLimitExceeded
+        );
+        assert_ok!(Zeropool::direct_deposit(Origin::signed(sender.clone()), receiver, 2_000));
+        assert_ok!(Zeropool::direct_deposit(Origin::signed(sender.clone()), receiver, 1_000));
+        assert_noop!(
+            Zeropool::direct_deposit(Origin::signed(sender), receiver, 1_000),
+            Error::<Test>::TvlCapExceeded
+        );
+    });
+}
+
+#[test]
+fn test_withdraw_limits() {
+    new_test_ext().execute_with(|| {
+        init_state();
+
+        assert_ok!(Zeropool::transact(owner(), DEPOSIT.to_vec()));
+        assert_ok!(Zeropool::transact(owner(), TRANSFER.to_vec()));
+
+        let amount = 3_000_000_000;
+        let limits = PoolLimits {
+            withdraw_window: 100,
+            window_limit: Some(amount - 1),
+            ..Default::default()
+        };
+        assert_ok!(Zeropool::set_limits(Origin::root(), limits.clone()));
+        assert_noop!(
+            Zeropool::transact(owner(), WITHDRAW.to_vec()),
+            Error::<Test>::WithdrawWindowLimitExceeded
+        );
+
+        let limits =
+            PoolLimits { window_limit: None, account_window_limit: Some(amount - 1), ..limits };
+        assert_ok!(Zeropool::set_limits(Origin::root(), limits.clone()));
+        assert_noop!(
+            Zeropool::transact(owner(), WITHDRAW.to_vec()),
+            Error::<Test>::AccountWithdrawWindowLimitExceeded
+        );
+
+        let limits =
+            PoolLimits { max_tx_amount: Some(amount - 1), account_window_limit: None, ..limits };
+        assert_ok!(Zeropool::set_limits(Origin::root(), limits.clone()));
+        assert_noop!(
+            Zeropool::transact(owner(), WITHDRAW.to_vec()),
+            Error::<Test>::TxAmountLimitExceeded
+        );
+
+        let limits = PoolLimits { max_tx_amount: None, window_limit: Some(amount), ..limits };
+        assert_ok!(Zeropool::set_limits(Origin::root(), limits));
+        assert_ok!(Zeropool::transact(owner(), WITHDRAW.to_vec()));
+        assert_eq!(WithdrawWindow::<Test>::get(), WindowUsage { start: 1, amount });
+    });
+}
+
+#[test]
+fn test_protocol_fee() {
+    new_test_ext().execute_with(|| {
+        let fee = ProtocolFee { operator_fee_share: Permill::from_percent(10), amount_bps: 30 };
+
+        assert_noop!(Zeropool::set_protocol_fee(owner(), fee), BadOrigin);
+        assert_noop!(
+            Zeropool::set_protocol_fee(
+                Origin::root(),
+                ProtocolFee { amount_bps: ProtocolFee::MAX_BPS + 1, ..fee }
+            ),
+            Error::<Test>::InvalidProtocolFee
+        );
+        assert_ok!(Zeropool::set_protocol_fee(Origin::root(), fee));
+        assert_eq!(ProtocolFees::<Test>::get(), fee);
+
+        assert_eq!(fee.amount_fee(1_000_000u128), 3_000);
+        assert_eq!(fee.operator_fee_share * 1_000u128, 100);
+    });
+}
+
+#[test]
+fn test_fee_policy() {
+    new_test_ext().execute_with(|| {
+        let policy = FeePolicy { base_fee: u64::MAX, per_byte_fee: 0 };
+        assert_ok!(ZeropoolOperatorManager::add_operator(owner(), OWNER, Default::default()));
+
+        assert_noop!(Zeropool::set_fee_policy(owner(), policy), BadOrigin);
+        assert_ok!(Zeropool::set_fee_policy(Origin::root(), policy));
+        assert_noop!(Zeropool::transact(owner(), DEPOSIT.to_vec()), Error::<Test>::FeeTooLow);
+
+        // The operator's own schedule applies on top of the global policy
+        assert_ok!(Zeropool::set_fee_policy(Origin::root(), Default::default()));
+        let info = OperatorInfo { fee: policy, ..Default::default() };
+        assert_ok!(ZeropoolOperatorManager::update_operator_info(owner(), info));
+        assert_noop!(Zeropool::transact(owner(), DEPOSIT.to_vec()), Error::<Test>::FeeTooLow);
+
+        assert_noop!(Zeropool::transact(owner(), vec![0; 8]), Error::<Test>::InvalidTxFormat);
+    });
+}
+
+#[test]
+fn test_pending_withdrawal() {
+    new_test_ext().execute_with(|| {
+        let dest = AccountId32::new([5; 32]);
+        Balances::make_free_balance_be(&Zeropool::account_id(), 1_000_000);
+        PendingWithdrawals::<Test>::insert(
+            0,
+            PendingWithdrawal { dest: dest.clone(), amount: 1000, unlock_at: 10, frozen: false },
+        );
+
+        assert_noop!(Zeropool::claim_withdrawal(owner(), 0), Error::<Test>::WithdrawalLocked);
+
+        System::set_block_number(10);
+        assert_ok!(Zeropool::freeze_withdrawal(owner(), 0));
+        assert_noop!(Zeropool::claim_withdrawal(owner(), 0), Error::<Test>::WithdrawalFrozen);
+
+        assert_noop!(Zeropool::thaw_withdrawal(owner(), 0), BadOrigin);
+        assert_ok!(Zeropool::thaw_withdrawal(Origin::root(), 0));
+        assert_ok!(Zeropool::claim_withdrawal(owner(), 0));
+
+        assert_eq!(Balances::free_balance(&dest), 1000);
+        assert!(PendingWithdrawals::<Test>::get(0).is_none());
+    });
+}
+
+#[test]
+fn test_large_withdrawal_queued() {
+    new_test_ext().execute_with(|| {
+        init_state();
+
+        assert_ok!(Zeropool::transact(owner(), DEPOSIT.to_vec()));
+        assert_ok!(Zeropool::transact(owner(), TRANSFER.to_vec()));
+
+        let amount = 3_000_000_000;
+        let delay = WithdrawalDelay { threshold: Some(amount - 1), delay: 10 };
+        assert_ok!(Zeropool::set_withdrawal_delay(Origin::root(), delay));
+
+        let balance = Balances::free_balance(&OWNER);
+        assert_ok!(Zeropool::transact(owner(), WITHDRAW.to_vec()));
+        assert_eq!(
+            PendingWithdrawals::<Test>::get(0),
+            Some(PendingWithdrawal { dest: OWNER, amount, unlock_at: 11, frozen: false })
+        );
+        assert_eq!(Balances::free_balance(&OWNER), balance);
+
+        System::set_block_number(11);
+        assert_ok!(Zeropool::claim_withdrawal(owner(), 0));
+        assert_eq!(Balances::free_balance(&OWNER), balance + amount);
+        assert_ok!(Zeropool::check_solvency());
+    });
+}
+
+#[test]
+fn test_claim_withdrawal_rechecked() {
+    new_test_ext().execute_with(|| {
+        init_state();
+
+        assert_ok!(Zeropool::transact(owner(), DEPOSIT.to_vec()));
+        assert_ok!(Zeropool::transact(owner(), TRANSFER.to_vec()));
+
+        let amount = 3_000_000_000;
+        let delay = WithdrawalDelay { threshold: Some(amount - 1), delay: 10 };
+        assert_ok!(Zeropool::set_withdrawal_delay(Origin::root(), delay));
+        assert_ok!(Zeropool::transact(owner(), WITHDRAW.to_vec()));
+        System::set_block_number(11);
+
+        // Withdrawals were paused during the delay
+        let kinds = TxKinds { deposit: false, transfer: false, withdraw: true };
+        assert_ok!(Zeropool::pause(owner(), kinds));
+        assert_noop!(Zeropool::claim_withdrawal(owner(), 0), Error::<Test>::Paused);
+        assert_ok!(Zeropool::unpause(Origin::root(), kinds));
+
+        // The destination was denied during the delay
+        assert_ok!(ZeropoolCompliance::set_denied(Origin::root(), OWNER, true));
+        assert_noop!(Zeropool::claim_withdrawal(owner(), 0), Error::<Test>::WithdrawalNotAllowed);
+
+        assert_ok!(ZeropoolCompliance::set_denied(Origin::root(), OWNER, false));
+        assert_ok!(Zeropool::claim_withdrawal(owner(), 0));
+        assert_eq!(PendingWithdrawals::<Test>::get(0), None);
+    });
+}
+
+#[test]
+fn test_withdraw_below_existential_deposit() {
+    new_test_ext().execute_with(|| {
+        init_state();
+
+        assert_ok!(Zeropool::transact(owner(), DEPOSIT.to_vec()));
+        assert_ok!(Zeropool::transact(owner(), TRANSFER.to_vec()));
+
+        // The withdrawal of 3000000000 can't create the destination account
+        Balances::unreserve(&OWNER, Balances::reserved_balance(&OWNER));
+        Balances::make_free_balance_be(&OWNER, 0);
+        ExistentialDeposit::set(3_000_000_001);
+        assert_noop!(
+            Zeropool::transact(owner(), WITHDRAW.to_vec()),
+            Error::<Test>::WithdrawBelowExistentialDeposit
+        );
+        ExistentialDeposit::set(1);
+    });
+}
+
+#[test]
+fn test_queued_withdraw_below_existential_deposit() {
+    new_test_ext().execute_with(|| {
+        init_state();
+
+        assert_ok!(Zeropool::transact(owner(), DEPOSIT.to_vec()));
+        assert_ok!(Zeropool::transact(owner(), TRANSFER.to_vec()));
+
+        let amount = 3_000_000_000;
+        let delay = WithdrawalDelay { threshold: Some(amount - 1), delay: 10 };
+        assert_ok!(Zeropool::set_withdrawal_delay(Origin::root(), delay));
+
+        // A queued withdrawal of 3000000000 can't create the destination account either
+        Balances::unreserve(&OWNER, Balances::reserved_balance(&OWNER));
+        Balances::make_free_balance_be(&OWNER, 0);
+        ExistentialDeposit::set(3_000_000_001);
+        assert_noop!(
+            Zeropool::transact(owner(), WITHDRAW.to_vec()),
+            Error::<Test>::WithdrawBelowExistentialDeposit
+        );
+        ExistentialDeposit::set(1);
+    });
+}
+
+#[test]
+fn test_energy_reward() {
+    new_test_ext().execute_with(|| {
+        let dest = AccountId32::new([5; 32]);
+        let reward = EnergyRewardRate::get().saturating_mul_int(100u128);
+
+        Zeropool::reward_energy(&dest, U256::from(100));
+        assert_eq!(Balances::free_balance(&dest), reward);
+        assert_eq!(TotalEnergyWithdrawn::<Test>::get(), U256::from(100));
+        assert_eq!(TotalEnergyRewards::<Test>::get(), reward);
+
+        // An empty reward account doesn't block the withdrawals
+        Balances::make_free_balance_be(&RewardAccount::get(), 0);
+        Zeropool::reward_energy(&dest, U256::from(100));
+        assert_eq!(Balances::free_balance(&dest), reward);
+        assert_eq!(TotalEnergyWithdrawn::<Test>::get(), U256::from(200));
+        assert_eq!(TotalEnergyRewards::<Test>::get(), reward);
+    });
+}
+
+#[test]
+fn test_queued_energy_reward() {
+    new_test_ext().execute_with(|| {
+        init_state();
+
+        assert_ok!(Zeropool::transact(owner(), DEPOSIT.to_vec()));
+        assert_ok!(Zeropool::transact(owner(), TRANSFER.to_vec()));
+
+        let amount = 3_000_000_000;
+        let delay = WithdrawalDelay { threshold: Some(amount - 1), delay: 10 };
+        assert_ok!(Zeropool::set_withdrawal_delay(Origin::root(), delay));
+        assert_ok!(Zeropool::transact(owner(), WITHDRAW.to_vec()));
+
+        // The fixture withdraws no energy, so its reward is added to the queued withdrawal
+        let reward = EnergyRewardRate::get().saturating_mul_int(100u128);
+        PendingEnergyRewards::<Test>::insert(0, U256::from(100));
+
+        // The reward of a queued withdrawal is paid when it's claimed
+        let balance = Balances::free_balance(&OWNER);
+        System::set_block_number(11);
+        assert_ok!(Zeropool::claim_withdrawal(owner(), 0));
+        assert_eq!(Balances::free_balance(&OWNER), balance + amount + reward);
+        assert!(PendingEnergyRewards::<Test>::get(0).is_none());
+        assert_eq!(TotalEnergyWithdrawn::<Test>::get(), U256::from(100));
+        assert_eq!(TotalEnergyRewards::<Test>::get(), reward);
+    });
+}
+
+#[test]
+fn test_pool_keep_alive() {
+    new_test_ext().execute_with(|| {
+        let dest = AccountId32::new([5; 32]);
+        Balances::make_free_balance_be(&Zeropool::account_id(), 1000);
+        PendingWithdrawals::<Test>::insert(
+            0,
+            PendingWithdrawal { dest: dest.clone(), amount: 1000, unlock_at: 0, frozen: false },
+        );
+
+        assert_noop!(
+            Zeropool::claim_withdrawal(owner(), 0),
+            pallet_balances::Error::<Test>::KeepAlive
+        );
+
+        Balances::make_free_balance_be(&Zeropool::account_id(), 1000 + ExistentialDeposit::get());
+        assert_ok!(Zeropool::claim_withdrawal(owner(), 0));
+        assert_eq!(Balances::free_balance(&Zeropool::account_id()), ExistentialDeposit::get());
+    });
+}
+
+#[test]
+fn test_operator_registry() {
+    new_test_ext().execute_with(|| {
+        let relayer = AccountId32::new([1; 32]);
+        let info = OperatorInfo {
+            name: b"relayer".to_vec().try_into().unwrap(),
+            relayer_url: b"https://relayer.example".to_vec().try_into().unwrap(),
+            fee: Default::default(),
+        };
+
+        assert_noop!(
+            ZeropoolOperatorManager::add_operator(
+                Origin::signed(relayer.clone()),
+                relayer.clone(),
+                info.clone()
+            ),
+            operator::Error::<Test>::NotOwner
+        );
+        assert_ok!(ZeropoolOperatorManager::add_operator(owner(), relayer.clone(), info.clone()));
+        assert_noop!(
+            ZeropoolOperatorManager::add_operator(owner(), relayer.clone(), info.clone()),
+            operator::Error::<Test>::AlreadyOperator
+        );
+        assert!(ZeropoolOperatorManager::is_operator(relayer.clone()));
+        assert_eq!(Operators::<Test>::get(&relayer), Some(info));
+
+        for i in 2..=MaxOperators::get() {
+            let other = AccountId32::new([i as u8; 32]);
+            assert_ok!(ZeropoolOperatorManager::add_operator(owner(), other, Default::default()));
+        }
+        assert_noop!(
+            ZeropoolOperatorManager::add_operator(owner(), OWNER, Default::default()),
+            operator::Error::<Test>::TooManyOperators
+        );
+
+        assert_ok!(ZeropoolOperatorManager::remove_operator(owner(), relayer.clone()));
+        assert!(!ZeropoolOperatorManager::is_operator(relayer.clone()));
+        assert!(!OperatorList::<Test>::get().contains(&relayer));
+    });
+}
+
+#[test]
+fn test_native_rate() {
+    new_test_ext().execute_with(|| {
+        let relayer = AccountId32::new([1; 32]);
+        let rate = FixedU128::saturating_from_rational(3, 2);
+
+        assert_noop!(
+            ZeropoolOperatorManager::set_native_rate(Origin::signed(relayer.clone()), Some(rate)),
+            operator::Error::<Test>::NotOperator
+        );
+
+        assert_ok!(ZeropoolOperatorManager::add_operator(
+            owner(),
+            relayer.clone(),
+            Default::default()
+        ));
+        assert_ok!(ZeropoolOperatorManager::set_native_rate(
+            Origin::signed(relayer.clone()),
+            Some(rate)
+        ));
+        assert_eq!(ZeropoolOperatorManager::native_rate(relayer.clone()), Some(rate));
+
+        assert_ok!(ZeropoolOperatorManager::remove_operator(owner(), relayer.clone()));
+        assert_eq!(ZeropoolOperatorManager::native_rate(relayer), None);
+    });
+}
+
+#[test]
+fn test_native_top_up() {
+    new_test_ext().execute_with(|| {
+        let relayer = AccountId32::new([1; 32]);
+        let native_amount = crate::num::U256::from(10u32);
+        let withdraw_amount = crate::num::U256::from(100u32);
+        assert_ok!(ZeropoolOperatorManager::add_operator(
+            owner(),
+            relayer.clone(),
+            Default::default()
+        ));
+
+        // Without a rate the whole withdrawal is paid in the pool token
+        assert_eq!(Zeropool::native_top_up(&relayer, native_amount, withdraw_amount), Ok((0, 0)));
+
+        assert_ok!(ZeropoolOperatorManager::set_native_rate(
+            Origin::signed(relayer.clone()),
+            Some(FixedU128::saturating_from_rational(3, 2))
+        ));
+        assert_eq!(
+            Zeropool::native_top_up(&relayer, native_amount, withdraw_amount),
+            Ok((10_000, 15_000))
+        );
+        assert_err!(
+            Zeropool::native_top_up(&relayer, withdraw_amount, withdraw_amount),
+            Error::<Test>::NativeAmountTooLarge
+        );
+
+        // Rates above `MaxNativeRate` are ignored
+        let rate = FixedU128::from_inner(MaxNativeRate::get().into_inner() + 1);
+        assert_ok!(ZeropoolOperatorManager::set_native_rate(
+            Origin::signed(relayer.clone()),
+            Some(rate)
+        ));
+        assert_eq!(Zeropool::native_top_up(&relayer, native_amount, withdraw_amount), Ok((0, 0)));
+    });
+}
+
+#[test]
+fn test_operator_slots() {
+    new_test_ext().execute_with(|| {
+        let first = AccountId32::new([1; 32]);
+        let second = AccountId32::new([2; 32]);
+        let anyone = AccountId32::new([3; 32]);
+        assert_ok!(ZeropoolOperatorManager::add_operator(
+            owner(),
+            first.clone(),
+            Default::default()
+        ));
+        assert_ok!(ZeropoolOperatorManager::add_operator(
+            owner(),
+            second.clone(),
+            Default::default()
+        ));
+
+        System::set_block_number(1);
+        assert!(ZeropoolOperatorManager::is_operator(first.clone()));
+        assert!(!ZeropoolOperatorManager::is_operator(second.clone()));
+
+        System::set_block_number(SlotLength::get());
+        assert!(!ZeropoolOperatorManager::is_operator(first.clone()));
+        assert!(ZeropoolOperatorManager::is_operator(second.clone()));
+
+        // Transactions of other accounts don't keep the slot closed
+        System::set_block_number(12);
+        ZeropoolOperatorManager::note_transaction(anyone.clone(), false);
+        ZeropoolOperatorManager::note_transaction(second.clone(), false);
+
+        System::set_block_number(16);
+        assert!(!ZeropoolOperatorManager::is_open_mode());
+        assert!(!ZeropoolOperatorManager::is_operator(anyone.clone()));
+
+        System::set_block_number(17);
+        assert!(ZeropoolOperatorManager::is_open_mode());
+        assert!(ZeropoolOperatorManager::is_operator(anyone.clone()));
+
+        // Open mode ends with the slot
+        System::set_block_number(2 * SlotLength::get());
+        assert!(!ZeropoolOperatorManager::is_operator(anyone));
+        assert!(ZeropoolOperatorManager::is_operator(first));
+    });
+}
+
+#[test]
+fn test_operator_bonding() {
+    new_test_ext().execute_with(|| {
+        let relayer = AccountId32::new([1; 32]);
+        let stepping_in = AccountId32::new([2; 32]);
+        let bond = OperatorBond::get();
+
+        assert_ok!(ZeropoolOperatorManager::add_operator(
+            owner(),
+            relayer.clone(),
+            Default::default()
+        ));
+        assert_eq!(Balances::reserved_balance(&relayer), bond);
+        assert_eq!(Bonds::<Test>::get(&relayer), Some(bond));
+
+        let poor = AccountId32::new([9; 32]);
+        assert_noop!(
+            ZeropoolOperatorManager::add_operator(owner(), poor, Default::default()),
+            operator::Error::<Test>::InsufficientBond
+        );
+
+        // Draining forced transactions doesn't count against the slot owner
+        let slot_start = SlotLength::get();
+        System::set_block_number(slot_start + OpenModeTimeout::get());
+        ZeropoolOperatorManager::note_transaction(stepping_in.clone(), true);
+        assert_eq!(Bonds::<Test>::get(&relayer), Some(bond));
+
+        // The slot owner has been idle since the slot started, someone else steps in
+        assert!(ZeropoolOperatorManager::is_operator(stepping_in.clone()));
+        ZeropoolOperatorManager::note_transaction(stepping_in.clone(), false);
+        ZeropoolOperatorManager::note_transaction(stepping_in.clone(), false);
+        assert_eq!(Bonds::<Test>::get(&relayer), Some(bond - MissedSlotSlash::get()));
+
+        // The slot owner was active in the next slot before going idle
+        System::set_block_number(2 * slot_start);
+        ZeropoolOperatorManager::note_transaction(relayer.clone(), false);
+        System::set_block_number(2 * slot_start + OpenModeTimeout::get());
+        assert!(ZeropoolOperatorManager::is_operator(stepping_in.clone()));
+        ZeropoolOperatorManager::note_transaction(stepping_in, false);
+        assert_eq!(Bonds::<Test>::get(&relayer), Some(bond - MissedSlotSlash::get()));
+
+        assert_noop!(
+            ZeropoolOperatorManager::slash_operator(owner(), relayer.clone(), 10),
+            BadOrigin
+        );
+        assert_ok!(ZeropoolOperatorManager::slash_operator(Origin::root(), relayer.clone(), 10));
+        let remaining = bond - MissedSlotSlash::get() - 10;
+
+        assert_ok!(ZeropoolOperatorManager::remove_operator(owner(), relayer.clone()));
+        assert_eq!(Bonds::<Test>::get(&relayer), None);
+        assert_noop!(
+            ZeropoolOperatorManager::withdraw_unbonded(Origin::signed(relayer.clone())),
+            operator::Error::<Test>::BondLocked
+        );
+
+        // Still slashable while unbonding
+        assert_ok!(ZeropoolOperatorManager::slash_operator(Origin::root(), relayer.clone(), 10));
+        let remaining = remaining - 10;
+
+        System::set_block_number(2 * slot_start + OpenModeTimeout::get() + UnbondingDelay::get());
+        assert_ok!(ZeropoolOperatorManager::withdraw_unbonded(Origin::signed(relayer.clone())));
+        assert_eq!(Balances::reserved_balance(&relayer), 0);
+        assert_eq!(Balances::free_balance(&relayer), 1000 - bond + remaining);
+    });
+}
+
+#[test]
+fn test_ownership_transfer() {
+    new_test_ext().execute_with(|| {
+        let new_owner = AccountId32::new([1; 32]);
+        let typo = AccountId32::new([2; 32]);
+
+        assert_noop!(
+            Zeropool::propose_owner(Origin::signed(new_owner.clone()), new_owner.clone()),
+            Error::<Test>::NotOwner
+        );
+        assert_ok!(Zeropool::propose_owner(owner(), typo));
+        assert_ok!(Zeropool::propose_owner(owner(), new_owner.clone()));
+        assert_eq!(PendingOwner::<Test>::get(), Some(new_owner.clone()));
+        assert_eq!(Owner::<Test>::get(), OWNER);
+
+        assert_noop!(
+            Zeropool::accept_ownership(Origin::signed(AccountId32::new([2; 32]))),
+            Error::<Test>::NotPendingOwner
+        );
+        assert_ok!(Zeropool::accept_ownership(Origin::signed(new_owner.clone())));
+        assert_eq!(Owner::<Test>::get(), new_owner.clone());
+        assert_eq!(PendingOwner::<Test>::get(), None);
+
+        // The operator manager follows the pool owner
+        assert_noop!(
+            ZeropoolOperatorManager::add_operator(owner(), OWNER, Default::default()),
+            operator::Error::<Test>::NotOwner
+        );
+        assert_ok!(ZeropoolOperatorManager::add_operator(
+            Origin::signed(new_owner.clone()),
+            new_owner,
+            Default::default()
+        ));
+    });
+}
+
+#[test]
+fn test_forced_tx() {
+    new_test_ext().execute_with(|| {
+        let user = AccountId32::new([1; 32]);
+        let anyone = AccountId32::new([2; 32]);
+        assert_ok!(ZeropoolOperatorManager::add_operator(owner(), OWNER, Default::default()));
+
+        assert_noop!(
+            Zeropool::queue_forced_tx(Origin::signed(user.clone()), DEPOSIT[..400].to_vec()),
+            Error::<Test>::InvalidTxFormat
+        );
+
+        assert_ok!(Zeropool::queue_forced_tx(Origin::signed(user.clone()), DEPOSIT.to_vec()));
+        assert_eq!(Balances::reserved_balance(&user), ForcedTxFee::get());
+
+        // The operators can still process other transactions for a while
+        assert_noop!(
+            Zeropool::transact(owner(), TRANSFER.to_vec()),
+            Error::<Test>::TransferVkNotSet
+        );
+        assert_noop!(
+            Zeropool::transact(Origin::signed(anyone.clone()), DEPOSIT.to_vec()),
+            Error::<Test>::NotOperator
+        );
+
+        System::set_block_number(1 + ForcedTxInclusionPeriod::get());
+        assert_noop!(
+            Zeropool::transact(owner(), TRANSFER.to_vec()),
+            Error::<Test>::ForcedTxOverdue
+        );
+
+        // A paused transaction stays in the queue
+        let kinds = TxKinds { deposit: true, transfer: false, withdraw: false };
+        assert_ok!(Zeropool::pause(owner(), kinds));
+        assert_noop!(
+            Zeropool::transact(Origin::signed(anyone.clone()), DEPOSIT.to_vec()),
+            Error::<Test>::Paused
+        );
+        assert_ok!(Zeropool::unpause(Origin::root(), kinds));
+
+        // Anyone can drain the queue. The transaction is invalid without the keys, so it's
+        // dropped and the fee goes to the caller.
+        let balance = Balances::free_balance(&anyone);
+        let info = Zeropool::transact(Origin::signed(anyone.clone()), DEPOSIT.to_vec()).unwrap();
+        assert_eq!(info.pays_fee, Pays::Yes);
+        assert!(ForcedTxs::<Test>::get(0).is_none());
+        assert_eq!(ForcedTxHead::<Test>::get(), 1);
+        assert_eq!(Balances::reserved_balance(&user), 0);
+        assert_eq!(Balances::free_balance(&anyone), balance + ForcedTxFee::get());
+
+        assert_noop!(
+            Zeropool::transact(owner(), TRANSFER.to_vec()),
+            Error::<Test>::TransferVkNotSet
+        );
+    });
+}
+
+#[test]
+fn test_commitments_range() {
+    new_test_ext().execute_with(|| {
+        for i in 0..4u32 {
+            Commitments::<Test>::insert(U256::from(i * 128), U256::from(i + 1));
+        }
+        PoolIndex::<Test>::put(U256::from(4 * 128));
+
+        let commitments = Zeropool::commitments(U256::from(130), 2);
+        assert_eq!(
+            commitments
+                .iter()
+                .map(|(index, commitment, _)| (*index, *commitment))
+                .collect::<Vec<_>>(),
+            vec![(U256::from(128), U256::from(2)), (U256::from(256), U256::from(3))]
+        );
+        assert_eq!(commitments[0].2, Commitments::<Test>::hashed_key_for(U256::from(128)));
+
+        assert_eq!(Zeropool::commitments(U256::from(3 * 128), 10).len(), 1);
+        assert!(Zeropool::commitments(U256::from(4 * 128), 10).is_empty());
+
+        // Missing commitments count against the limit
+        Commitments::<Test>::remove(U256::from(128));
+        let indices = Zeropool::commitments(U256::zero(), 2)
+            .into_iter()
+            .map(|(index, _, _)| index)
+            .collect::<Vec<_>>();
+        assert_eq!(indices, vec![U256::zero()]);
+    });
+}
+
+#[test]
+fn test_root_history() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(ZeropoolOperatorManager::add_operator(owner(), OWNER, Default::default()));
+
+        // The deposit is proven against the first root
+        PoolIndex::<Test>::put(U256::from(RootHistorySize::get() * 128));
+        assert_noop!(
+            Zeropool::transact(owner(), DEPOSIT.to_vec()),
+            Error::<Test>::TransferIndexTooOld
+        );
+
+        PoolIndex::<Test>::put(U256::from((RootHistorySize::get() - 1) * 128));
+        assert_noop!(
+            Zeropool::transact(owner(), DEPOSIT.to_vec()),
+            Error::<Test>::TransferVkNotSet
+        );
+
+        for i in 1..=10u32 {
+            Roots::<Test>::insert(U256::from(i * 128), U256::from(i));
+        }
+        PoolIndex::<Test>::put(U256::from(10 * 128));
+        crate::migrations::v2::migrate::<Test>();
+        assert_eq!(RootsToPrune::<Test>::get(), Some((U256::zero(), U256::from(7 * 128))));
+
+        assert_eq!(Roots::<Test>::iter_keys().count(), 10);
+
+        // The expired roots are removed when the blocks have weight to spare
+        Zeropool::on_idle(1, Weight::MAX);
+        assert_eq!(RootsToPrune::<Test>::get(), None);
+
+        let mut indices = Roots::<Test>::iter_keys().collect::<Vec<_>>();
+        indices.sort();
+        assert_eq!(indices, (7..=10u32).map(|i| U256::from(i * 128)).collect::<Vec<_>>());
+    });
+}
+
+#[test]
+fn test_migrations() {
+    new_test_ext().execute_with(|| {
+        assert_eq!(Zeropool::on_chain_storage_version(), crate::STORAGE_VERSION);
+
+        let zero = crate::num::U256::ZERO;
+        let legacy_vk = Groth16VK {
+            alpha: [zero; 2],
+            beta: [zero; 4],
+            gamma: [zero; 4],
+            delta: [zero; 4],
+            ic: vec![[zero; 2]],
+        };
+        StorageVersion::new(0).put::<Zeropool>();
+        unhashed::put(&TransferVk::<Test>::hashed_key(), &legacy_vk);
+
+        crate::migrations::migrate::<Test>();
+
+        assert_eq!(TransferVk::<Test>::get(), Some(VK::Groth16(legacy_vk)));
+        assert_eq!(TreeVk::<Test>::get(), None);
+        assert_eq!(Zeropool::on_chain_storage_version(), crate::STORAGE_VERSION);
+    });
+}
+
+#[test]
+fn test_operator_migration() {
+    new_test_ext().execute_with(|| {
+        let relayer = AccountId32::new([1; 32]);
+        let pallet = <ZeropoolOperatorManager as PalletInfoAccess>::name().as_bytes();
+        assert_eq!(ZeropoolOperatorManager::on_chain_storage_version(), operator::STORAGE_VERSION);
+
+        StorageVersion::new(0).put::<ZeropoolOperatorManager>();
+        migration::put_storage_value(pallet, b"Owner", &[], OWNER);
+        migration::put_storage_value(pallet, b"Operator", &[], relayer.clone());
+
+        ZeropoolOperatorManager::on_runtime_upgrade();
+
+        assert!(Operators::<Test>::contains_key(&relayer));
+        assert_eq!(OperatorList::<Test>::get().into_inner(), vec![relayer.clone()]);
+        assert_eq!(Bonds::<Test>::get(&relayer), Some(OperatorBond::get()));
+        assert_eq!(Balances::reserved_balance(&relayer), OperatorBond::get());
+        assert!(!migration::have_storage_value(pallet, b"Owner", &[]));
+        assert!(!migration::have_storage_value(pallet, b"Operator", &[]));
+        assert_eq!(ZeropoolOperatorManager::on_chain_storage_version(), operator::STORAGE_VERSION);
+
+        // Runs only once
+        migration::put_storage_value(pallet, b"Operator", &[], OWNER);
+        ZeropoolOperatorManager::on_runtime_upgrade();
+        assert!(!Operators::<Test>::contains_key(&OWNER));
+
+        // An operator without the bond is not registered
+        let poor = AccountId32::new([7; 32]);
+        StorageVersion::new(0).put::<ZeropoolOperatorManager>();
+        migration::put_storage_value(pallet, b"Operator", &[], poor.clone());
+        ZeropoolOperatorManager::on_runtime_upgrade();
+        assert!(!Operators::<Test>::contains_key(&poor));
+        assert!(!migration::have_storage_value(pallet, b"Operator", &[]));
+    });
+}
+
+#[test]
+fn test_direct_deposit() {
+    new_test_ext().execute_with(|| {
+        let sender = AccountId32::new([6; 32]);
+        let receiver = ZkAddress { diversifier: [1; 10], pk: [2; 32] };
+        Balances::make_free_balance_be(&sender, 10_000);
+
+        assert_noop!(
+            Zeropool::direct_deposit(Origin::signed(sender.clone()), receiver.clone(), 1500),
+            Error::<Test>::IncorrectAmount
+        );
+        assert_noop!(
+            Zeropool::direct_deposit(Origin::signed(sender.clone()), receiver.clone(), 0),
+            Error::<Test>::IncorrectAmount
+        );
+
+        assert_ok!(Zeropool::direct_deposit(
+            Origin::signed(sender.clone()),
+            receiver.clone(),
+            3000
+        ));
+        assert_eq!(Balances::free_balance(&sender), 7000);
+        assert_eq!(
+            DirectDeposits::<Test>::get(0),
+            Some(DirectDeposit { sender: sender.clone(), receiver, amount: 3000, created_at: 1 })
+        );
+        assert_eq!(NextDirectDepositId::<Test>::get(), 1);
+
+        assert_ok!(ZeropoolOperatorManager::add_operator(owner(), OWNER, Default::default()));
+        assert_noop!(
+            Zeropool::process_direct_deposits(
+                owner(),
+                2,
+                U256::zero(),
+                U256::zero(),
+                vec![],
+                vec![]
+            ),
+            Error::<Test>::InvalidTxFormat
+        );
+
+        assert_noop!(Zeropool::refund_direct_deposit(owner(), 0), Error::<Test>::NotDepositor);
+        assert_noop!(
+            Zeropool::refund_direct_deposit(Origin::signed(sender.clone()), 0),
+            Error::<Test>::DirectDepositNotExpired
+        );
+
+        System::set_block_number(1 + DirectDepositTimeout::get());
+        assert_ok!(Zeropool::refund_direct_deposit(Origin::signed(sender.clone()), 0));
+        assert_eq!(Balances::free_balance(&sender), 10_000);
+        assert_eq!(DirectDeposits::<Test>::get(0), None);
+        assert_noop!(
+            Zeropool::refund_direct_deposit(Origin::signed(sender), 0),
+            Error::<Test>::DirectDepositNotFound
+        );
+    });
+}
+
+#[test]
+fn test_direct_deposit_fee_not_covered() {
+    new_test_ext().execute_with(|| {
+        let sender = AccountId32::new([6; 32]);
+        let receiver = ZkAddress { diversifier: [1; 10], pk: [2; 32] };
+        Balances::make_free_balance_be(&sender, 3000);
+        let fee = ProtocolFee { operator_fee_share: Permill::zero(), amount_bps: 100 };
+        assert_ok!(Zeropool::set_protocol_fee(Origin::root(), fee));
+
+        // The balance covers the amount but not the fee on top, nothing is charged
+        assert_noop!(
+            Zeropool::direct_deposit(Origin::signed(sender.clone()), receiver, 3000),
+            pallet_balances::Error::<Test>::InsufficientBalance
+        );
+        assert_eq!(Balances::free_balance(&sender), 3000);
+        assert_eq!(TotalProtocolFees::<Test>::get(), 0);
+        assert_eq!(DirectDeposits::<Test>::get(0), None);
+    });
+}
+
+#[test]
+fn test_process_direct_deposits() {
+    new_test_ext().execute_with(|| {
+        let sender = AccountId32::new([6; 32]);
+        Balances::make_free_balance_be(&sender, 10_000);
+        let fee = ProtocolFee { operator_fee_share: Permill::zero(), amount_bps: 100 };
+        assert_ok!(Zeropool::set_protocol_fee(Origin::root(), fee));
+
+        // The protocol fee is paid on top of the amount
+        for (diversifier, pk, amount) in [(1, 2, 3000), (3, 4, 2000)] {
+            let receiver = ZkAddress { diversifier: [diversifier; 10], pk: [pk; 32] };
+            assert_ok!(Zeropool::direct_deposit(Origin::signed(sender.clone()), receiver, amount));
+        }
+        assert_eq!(Balances::free_balance(&sender), 10_000 - 5_000 - 50);
+        assert_eq!(TotalProtocolFees::<Test>::get(), 50);
+
+        assert_ok!(ZeropoolOperatorManager::add_operator(owner(), OWNER, Default::default()));
+        assert_ok!(Zeropool::set_direct_deposit_vk(owner(), DIRECT_DEPOSIT_VK.to_vec()));
+        assert_ok!(Zeropool::set_tree_vk(owner(), BATCH_TREE_VK.to_vec()));
+
+        // The batch proof commits to the deposits and the out commitment
+        assert_noop!(
+            Zeropool::process_direct_deposits(
+                owner(),
+                2,
+                U256::from(9),
+                U256::from(8),
+                BATCH_PROOF.to_vec(),
+                BATCH_TREE_PROOF.to_vec()
+            ),
+            Error::<Test>::InvalidProof
+        );
+        assert_ok!(Zeropool::process_direct_deposits(
+            owner(),
+            2,
+            U256::from(7),
+            U256::from(8),
+            BATCH_PROOF.to_vec(),
+            BATCH_TREE_PROOF.to_vec()
+        ));
+
+        assert_eq!(DirectDeposits::<Test>::iter().count(), 0);
+        assert_eq!(DirectDepositHead::<Test>::get(), 2);
+        assert_eq!(PoolIndex::<Test>::get(), U256::from(128));
+        assert_eq!(Roots::<Test>::get(U256::from(128)), U256::from(8));
+        assert_eq!(Commitments::<Test>::get(U256::zero()), Some(U256::from(7)));
+        assert_eq!(TotalDeposited::<Test>::get(), U256::from(5));
+        assert_eq!(ShieldedSupply::<Test>::get(), U256::from(5));
+    });
+}
+
+#[test]
+fn test_direct_deposit_holes() {
+    new_test_ext().execute_with(|| {
+        let sender = AccountId32::new([6; 32]);
+        let receiver = ZkAddress { diversifier: [1; 10], pk: [2; 32] };
+        Balances::make_free_balance_be(&sender, 100_000);
+        let max = MaxDirectDepositsPerBatch::get() as u64;
+        for _ in 0..(max + 3) {
+            assert_ok!(Zeropool::direct_deposit(
+                Origin::signed(sender.clone()),
+                receiver.clone(),
+                1000
+            ));
+        }
+
+        // Refunds behind a pending deposit leave holes
+        System::set_block_number(1 + DirectDepositTimeout::get());
+        for id in 1..(max + 2) {
+            assert_ok!(Zeropool::refund_direct_deposit(Origin::signed(sender.clone()), id));
+        }
+        assert_eq!(DirectDepositHead::<Test>::get(), 0);
+
+        // The batch scan skips at most `MaxDirectDepositsPerBatch` of them
+        assert_ok!(ZeropoolOperatorManager::add_operator(owner(), OWNER, Default::default()));
+        assert_noop!(
+            Zeropool::process_direct_deposits(
+                owner(),
+                2,
+                U256::from(7),
+                U256::from(8),
+                BATCH_PROOF.to_vec(),
+                BATCH_TREE_PROOF.to_vec()
+            ),
+            Error::<Test>::InvalidBatchSize
+        );
+
+        // Refunds move the head past up to `MaxDirectDepositsPerBatch` holes
+        assert_ok!(Zeropool::refund_direct_deposit(Origin::signed(sender.clone()), 0));
+        assert_eq!(DirectDepositHead::<Test>::get(), max);
+        assert_ok!(Zeropool::refund_direct_deposit(Origin::signed(sender), max + 2));
+        assert_eq!(DirectDepositHead::<Test>::get(), max + 3);
+    });
+}
+
+#[test]
+fn test_compliance() {
+    new_test_ext().execute_with(|| {
+        let sender = AccountId32::new([6; 32]);
+        let receiver = ZkAddress { diversifier: [1; 10], pk: [2; 32] };
+        Balances::make_free_balance_be(&sender, 10_000);
+
+        assert_noop!(ZeropoolCompliance::set_denied(owner(), sender.clone(), true), BadOrigin);
+        assert_ok!(ZeropoolCompliance::set_denied(Origin::root(), sender.clone(), true));
+        assert!(!ZeropoolCompliance::is_allowed(&sender));
+        assert_noop!(
+            Zeropool::direct_deposit(Origin::signed(sender.clone()), receiver, 1000),
+            Error::<Test>::DepositNotAllowed
+        );
+
+        assert_ok!(ZeropoolCompliance::set_denied(Origin::root(), sender.clone(), false));
+        assert_ok!(ZeropoolCompliance::set_allow_list_enabled(Origin::root(), true));
+        assert!(!ZeropoolCompliance::is_allowed(&sender));
+
+        assert_ok!(ZeropoolCompliance::set_allowed(Origin::root(), sender.clone(), true));
+        assert!(ZeropoolCompliance::is_allowed(&sender));
+        assert_ok!(Zeropool::direct_deposit(Origin::signed(sender), receiver, 1000));
+    });
+}
+
+#[test]
+fn test_compliance_withdraw() {
+    new_test_ext().execute_with(|| {
+        init_state();
+
+        assert_ok!(Zeropool::transact(owner(), DEPOSIT.to_vec()));
+        assert_ok!(Zeropool::transact(owner(), TRANSFER.to_vec()));
 
-        let transfer = hex!["00000000237d9ab8ca165b7728b5353ee84423ee3ee44a71c535894a08dc2c476be4c321179204c43f1f98e74eab0c9e668c6e63bdb16907443a977e323dd3023602fb6e000000000080000000000000000000000000000000000000000000002d10fca86c5f882296870b3cee750134cc5af7fd10b5df5fe0c7b1a465b478b829e384af859d777e7ebf339ae7c21bc45bbe2da588d9af5f4f1b556f9a3263232af3fab5aa4f0df62236ecb7938482c862a32207aa397aa0ba0fae47841802e111af294acf087e6cdb385c944eb997a18154fbea9c3f4c34b98517d062ce664321c65349a1070a08a6824a195ca4ccc8f3a4ee73f4d55ce19ec626513b5684772df66f128fbfeadb4c43afa8b0133c0c98872d2f07fbd5d9abdaf6a7434ee227112747c3e3ab974996366e183cbba0dc98cbdb50b6f8f68832924695475a49c908123f64dde854f360a3a5ff30fe0802d9b26cf931f3d1356527efc9f240cc0229099ed53518664aa52c9cdeb77ebbea7616fa25506073d8d29162e5c73b916105d6c07495bdd93eb60a3dff79771a88baba6e7ad3f4bb4855e18bba78226f2b0f2db408d145faf5835fd11d6a12a18e159ffc4869b398849239259cd5f6b0a61d86ec7af5dad43bfccb9d20d4a9dc0b004760c8b7683bf3147783b1d4d2603c19a0829f443e907797acbda88bd31f67646b509ff2636fa554b4e30d95e3fff527f9cbce3cbaa8a58ad74ea2d1d549e2cfa2b8cd46a7ded72c39e6b752cf3a2f271fdb02f7e34d938bd5fe16d60786faa1addb87eabc153ad161eeaccf247d8e2a5aac24078162d70963760ca6221d35b00a41f9fe6643facb8ddac452a4fc8402dfa64efc00b497fc81884cc4820060b3fb809852d8445493c256ade00024070001017e0000000000000000020000002dedb8d83de8fd17b97a0c511bd8dfcbbf5a857c34c60c7b429d4dbf811aae28f627302e3a6384d3b7b97d571e9ccb8651f2405febfd9596bece416900684417730f108b1f1edba449c5a75cc27e4cf8486ea2a883561b1667ea1cd338eff42fae7b03ffdbafc43c8f2f3cea96cef62584bcda71768439882b78926602d20ac3523734ad517e701319d3984967ff6601760a726ef1d6b18476338ba321b0fd88088062ba8b8b78c751863c574a9618f4598c43d314f290bb3ee40a11a632600c34871f184450c7306e6904f4e737505043aed92bfa356689ce706a70d7ce8ac80d65a0a2877e36e980aad643b6423ae12ef414f665c018de9e15d0f03b9e3351cd55a134e8c0e8bd1cf8a37126f31f50f8a492d6f1c737bf81e1af3f98b300691c76983003053091f2007a0dce6bfa90c0219d78ce02ea0e05299565f5a9e2a4cf1347dc3ac33488f165f04f0171ca0070fd1fe19aeec1ce1ecbf306167cd09151f181b4be4c1e7e93bb4f430863333eab18"].into();
-        assert_ok!(Zeropool::transact(owner(), transfer));
+        // The withdrawal is sent back to the depositor
+        assert_ok!(ZeropoolCompliance::set_denied(Origin::root(), OWNER, true));
+        assert_noop!(
+            Zeropool::transact(owner(), WITHDRAW.to_vec()),
+            Error::<Test>::WithdrawalNotAllowed
+        );
 
-        let withdraw = hex!["000000002c985dbf440ba5d71866be3aab012bc487f32daf57f912c9efe3badd920c63db07b95d4d84ff468f72c226f8e0cec59e6f8d0b2af55a4a99f0c4335df79308840000000001000000000000000000000000000000ffffffffffd239401e76605d181bf056f5db4aeea7ab11f6f998cea56427fee2e0ab17e5f0593a701df6a9fec0f64939ddf60bc3a1a06a161074f30b97ffcdca95fb2082affae1ed08b0b51ccef92b2d29117c26f8471dc86db8f650b995eaeec4648ddded95a8650297b462124bc86c3f6875ebfd7c95860a7e6cb12041ca8eeb434f5c96f296b601548f068ce0d1d14e6a00f3866a32083bdce217480705c336a177ecac8295b712bbf5b47fb2475672c19e2f79429b668a815c18dfdbf569bc3031a8b97038a61a87c42985b6fad4c846320f8a02e0bf8defb9adfb4ba61213c32969cd8f0bad0690a04fdc795a4fa481784d39dd9b8e5e65db3247c3039a8b6864416cd562bb1216a80df8beb5b525f72c5d33640d4eadd0fe25faff6ab9d4302e1866876e7b0012b14735565d70674622aa29e7a16f4abf5c263c6f013eabb74955b66f12a4130ddda82bd8054d0768290629178f041cb97473fea93f9af3fc1af9b4c4667721c426b74da384c7c305b3a1db26687dc4f1ff1e914bb7b6aed2681c50e762a41dfe9e0e28c3e6625fcdb4cecd2b180d584214c5d3bb810890eba26ec3daf6c5142fea4c03b570a5690199cd71b0d1b3ccdcdef666ed9ddf6ada0b8803568aaa12af788940c776ed416e8f8171efbca512e835bc7d21bde98392436209657acc2015cc37828dc572a319e1ab69c0375281dc9834b604e04b07b796bbfa14418b09b3a8f848d37fc8a341b31a285945fdb6a33534fa76e15dffd65dd26728cec6000200fa00000000000000000000000000000000d000ac5048ae858aca2e6aa43e00661562a47026fe88ff83992430204a1597520100000002e3e0d869ad18376d317b73d4d56aeca73ec5eb2b8b43216a920e79403fae0783621edf3a8435f9be5acad4a0d945007be6b6131a6fe40333dea8f479511f02d56165f76d4fb9fc8a893e96744cab0801af7bc884afb4c9a20da401f7520ba10d68c950a097a96b3722bad97cc4d51998d02188d2fd74915e82e856559a03c0bc2f24a38ff500254f70923a5ffd8ce7843da20330db7057283de00ad2c663bc449b2d4d3e64dd66d22a42b8aaeddfae65a5b7714b069ca62ab37fac682a26437eaf4f81daa8"].into();
-        assert_ok!(Zeropool::transact(owner(), withdraw));
+        assert_ok!(ZeropoolCompliance::set_denied(Origin::root(), OWNER, false));
+        assert_ok!(Zeropool::transact(owner(), WITHDRAW.to_vec()));
     });
 }
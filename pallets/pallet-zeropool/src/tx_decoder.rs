@@ -8,12 +8,14 @@ use num_traits::FromPrimitive;
 
 // Sizes
 const NUM_SIZE: usize = 32;
-const PROOF_SIZE: usize = NUM_SIZE * 8;
+pub const PROOF_SIZE: usize = NUM_SIZE * 8;
 const DELTA_SIZE: usize = 28;
 const BALANCE_SIZE: usize = 8;
 const ADDRESS_SIZE: usize = 32;
 const SIGNATURE_SIZE: usize = 64;
 const MEMO_META_SIZE: usize = 8;
+/// Largest memo a transaction can carry, its size is encoded in two bytes.
+pub const MAX_MEMO_SIZE: usize = u16::MAX as usize;
 
 // Offsets
 // const SELECTOR: usize = 0;
@@ -109,6 +111,31 @@ impl<'a> TxDecoder<'a> {
         TxType::from_u16(num).unwrap()
     }
 
+    /// Transaction type, or `None` if the data is too short or the type is unknown.
+    #[inline]
+    pub fn checked_tx_type(&self) -> Option<TxType> {
+        let bytes = self.data.get(TX_TYPE..(TX_TYPE + 2))?;
+        TxType::from_u16(u16::from_be_bytes(bytes.try_into().ok()?))
+    }
+
+    /// Whether the data holds a transaction of a known type with all of its fields, so that
+    /// the accessors do not panic. Proofs and values are not checked.
+    pub fn is_valid(&self) -> bool {
+        if self.data.len() < MEMO {
+            return false
+        }
+
+        let (min_memo_size, trailer_size) = match self.checked_tx_type() {
+            Some(TxType::Deposit) => (BALANCE_SIZE, ADDRESS_SIZE + SIGNATURE_SIZE),
+            Some(TxType::Transfer) => (BALANCE_SIZE, 0),
+            Some(TxType::Withdraw) => (MEMO_ADDRESS + ADDRESS_SIZE - MEMO, 0),
+            None => return false,
+        };
+
+        let memo_size = self.memo_size();
+        memo_size >= min_memo_size && self.data.len() >= MEMO + memo_size + trailer_size
+    }
+
     #[inline]
     pub fn memo_size(&self) -> usize {
         u16::from_be_bytes(self.data[MEMO_SIZE..(MEMO_SIZE + 2)].try_into().unwrap()) as usize
@@ -119,6 +146,12 @@ impl<'a> TxDecoder<'a> {
         &self.data[MEMO..(MEMO + self.memo_size())]
     }
 
+    /// Whether the data is long enough to read the memo fee.
+    #[inline]
+    pub fn has_memo_fee(&self) -> bool {
+        self.data.len() >= MEMO_FEE + BALANCE_SIZE
+    }
+
     #[inline]
     pub fn memo_fee(&self) -> U256 {
         U256::from_big_endian(&self.data[MEMO_FEE..(MEMO_FEE + BALANCE_SIZE)])
@@ -169,7 +202,8 @@ fn ensure_twos_complement(n: U256, len: usize) -> U256 {
     }
 }
 
-fn decode_proof(data: &[u8]) -> Proof {
+/// Decodes a Groth16 proof in the transaction encoding. `data` must hold `PROOF_SIZE` bytes.
+pub fn decode_proof(data: &[u8]) -> Proof {
     let a = decode_point(data);
     let b = decode_point(&data[NUM_SIZE * 2..]);
     let c = decode_point(&data[NUM_SIZE * 6..]);
@@ -332,6 +366,26 @@ mod tests {
         assert_eq!(decoder.deposit_address(), &deposit_signature_block[0..32], "deposit_address",)
     }
 
+    #[test]
+    fn test_tx_decoder_is_valid() {
+        let mut data = vec![0u8; MEMO];
+        assert!(!TxDecoder::new(&data[..MEMO - 1]).is_valid(), "truncated");
+        assert!(!TxDecoder::new(&data).is_valid(), "empty memo");
+
+        data[MEMO_SIZE..MEMO].copy_from_slice(&(BALANCE_SIZE as u16).to_be_bytes());
+        data.extend_from_slice(&[0; BALANCE_SIZE]);
+        assert!(!TxDecoder::new(&data).is_valid(), "deposit without signature");
+
+        data[TX_TYPE..MEMO_SIZE].copy_from_slice(&(TxType::Transfer as u16).to_be_bytes());
+        assert!(TxDecoder::new(&data).is_valid(), "transfer");
+
+        data[TX_TYPE..MEMO_SIZE].copy_from_slice(&(TxType::Withdraw as u16).to_be_bytes());
+        assert!(!TxDecoder::new(&data).is_valid(), "withdraw without address");
+
+        data[TX_TYPE..MEMO_SIZE].copy_from_slice(&3u16.to_be_bytes());
+        assert!(!TxDecoder::new(&data).is_valid(), "unknown type");
+    }
+
     #[test]
     fn test_tx_decoder_decode_point() {
         use core::str::FromStr;
@@ -10,8 +10,21 @@ use scale_info::TypeInfo;
 pub type G1 = [U256; 2];
 pub type G2 = [U256; 4];
 
+/// Verification key tagged with the proof system it belongs to. Groth16 is the only system the
+/// transaction format supports, the tag lets another one be added without a storage migration.
 #[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Encode, Decode, PartialEq, TypeInfo)]
-pub struct VK {
+pub enum VK {
+    Groth16(Groth16VK),
+}
+
+impl MaxEncodedLen for VK {
+    fn max_encoded_len() -> usize {
+        1 + Groth16VK::max_encoded_len()
+    }
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Encode, Decode, PartialEq, TypeInfo)]
+pub struct Groth16VK {
     pub alpha: G1,
     pub beta: G2,
     pub gamma: G2,
@@ -21,7 +34,7 @@ pub struct VK {
 
 const MAX_IC_LEN: usize = 6;
 
-impl MaxEncodedLen for VK {
+impl MaxEncodedLen for Groth16VK {
     fn max_encoded_len() -> usize {
         let mut len = 0;
         len += G1::max_encoded_len();
@@ -31,6 +44,7 @@ impl MaxEncodedLen for VK {
     }
 }
 
+/// Groth16 proof, the only kind of proof the current transaction format carries.
 #[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
 pub struct Proof {
     pub a: G1,
@@ -38,6 +52,13 @@ pub struct Proof {
     pub c: G1,
 }
 
+/// Verifies the proof against the key.
+pub fn verify(vk: &VK, proof: &Proof, input: &[U256]) -> core::result::Result<bool, ZeroPoolError> {
+    match vk {
+        VK::Groth16(vk) => alt_bn128_groth16verify(vk, proof, input),
+    }
+}
+
 #[inline]
 pub fn alt_bn128_g1_multiexp(v: &[(G1, U256)]) -> core::result::Result<G1, ZeroPoolError> {
     let data = v.try_to_vec().unwrap();
@@ -66,7 +87,7 @@ pub fn alt_bn128_pairing_check(v: &[(G1, G2)]) -> core::result::Result<bool, Zer
 }
 
 pub fn alt_bn128_groth16verify(
-    vk: &VK,
+    vk: &Groth16VK,
     proof: &Proof,
     input: &[U256],
 ) -> core::result::Result<bool, ZeroPoolError> {
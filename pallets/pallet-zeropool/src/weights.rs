@@ -0,0 +1,79 @@
+//! Weights for pallet_zeropool
+//!
+//! NOT benchmark results. These are conservative hand-set upper bounds, kept until
+//! `scripts/bench` is run on the reference hardware and its output replaces this file. The
+//! transaction benchmarks process the `fixtures` transactions, the `memo` benchmark covers memos
+//! up to `tx_decoder::MAX_MEMO_SIZE` bytes.
+
+#![cfg_attr(rustfmt, rustfmt_skip)]
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::{traits::Get, weights::{Weight, constants::RocksDbWeight}};
+use core::marker::PhantomData;
+
+/// Weight functions needed for pallet_zeropool.
+pub trait WeightInfo {
+	fn transact_deposit() -> Weight;
+	fn transact_transfer() -> Weight;
+	fn transact_withdraw() -> Weight;
+	fn memo(m: u32, ) -> Weight;
+	fn process_direct_deposits() -> Weight;
+}
+
+/// Hand-set upper bounds for pallet_zeropool, see the module docs.
+pub struct SubstrateWeight<T>(PhantomData<T>);
+impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
+	fn transact_deposit() -> Weight {
+		(20_200_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(18 as Weight))
+			.saturating_add(T::DbWeight::get().writes(13 as Weight))
+	}
+	fn transact_transfer() -> Weight {
+		(20_000_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(12 as Weight))
+			.saturating_add(T::DbWeight::get().writes(9 as Weight))
+	}
+	fn transact_withdraw() -> Weight {
+		(20_200_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(24 as Weight))
+			.saturating_add(T::DbWeight::get().writes(19 as Weight))
+	}
+	fn memo(m: u32, ) -> Weight {
+		(0 as Weight)
+			.saturating_add((2_000 as Weight).saturating_mul(m as Weight))
+	}
+	fn process_direct_deposits() -> Weight {
+		(20_000_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(10 as Weight))
+			.saturating_add(T::DbWeight::get().writes(9 as Weight))
+	}
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+	fn transact_deposit() -> Weight {
+		(20_200_000_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(18 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(13 as Weight))
+	}
+	fn transact_transfer() -> Weight {
+		(20_000_000_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(12 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(9 as Weight))
+	}
+	fn transact_withdraw() -> Weight {
+		(20_200_000_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(24 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(19 as Weight))
+	}
+	fn memo(m: u32, ) -> Weight {
+		(0 as Weight)
+			.saturating_add((2_000 as Weight).saturating_mul(m as Weight))
+	}
+	fn process_direct_deposits() -> Weight {
+		(20_000_000_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(10 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(9 as Weight))
+	}
+}
@@ -15,9 +15,12 @@ use sp_consensus_aura::sr25519::AuthorityId as AuraId;
 use sp_core::{crypto::KeyTypeId, OpaqueMetadata};
 use sp_runtime::{
     create_runtime_str, generic, impl_opaque_keys,
-    traits::{AccountIdLookup, BlakeTwo256, Block as BlockT, IdentifyAccount, NumberFor, Verify},
+    traits::{
+        AccountIdConversion, AccountIdLookup, BlakeTwo256, Block as BlockT, IdentifyAccount,
+        NumberFor, Verify,
+    },
     transaction_validity::{TransactionSource, TransactionValidity},
-    ApplyExtrinsicResult, MultiSignature,
+    ApplyExtrinsicResult, FixedU128, MultiSignature,
 };
 use sp_std::prelude::*;
 #[cfg(feature = "std")]
@@ -27,7 +30,7 @@ use sp_version::RuntimeVersion;
 // A few exports that help ease life for downstream crates.
 pub use frame_support::{
     construct_runtime, parameter_types,
-    traits::{KeyOwnerProofSystem, Randomness, StorageInfo},
+    traits::{Currency, KeyOwnerProofSystem, OnUnbalanced, Randomness, StorageInfo},
     weights::{
         constants::{BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight, WEIGHT_PER_SECOND},
         IdentityFee, Weight,
@@ -290,6 +293,27 @@ parameter_types! {
     pub const ZeropoolPalletId: PalletId = PalletId(*b"zeropool");
     // Seed phrase: test test test test test test test test test test test junk
     pub const InitialOwner: AccountId = AccountId::new(hex!("d000ac5048ae858aca2e6aa43e00661562a47026fe88ff83992430204a159752"));
+    pub const ForcedTxFee: Balance = 100_000_000_000_000;
+    pub const ForcedTxInclusionPeriod: BlockNumber = HOURS;
+    pub const MaxForcedTxSize: u32 = 4096;
+    pub const RootHistorySize: u32 = 1024;
+    pub const MaxDirectDepositsPerBatch: u32 = 16;
+    pub const DirectDepositTimeout: BlockNumber = DAYS;
+    pub EnergyRewardAccount: AccountId = PalletId(*b"zp/rewrd").into_account();
+    // 10^-3 per unit of energy, that is about 0.0144 UNIT per shielded UNIT per day.
+    pub const EnergyRewardRate: FixedU128 = FixedU128::from_inner(1_000_000_000_000_000);
+    // Operators can charge up to 10% on top of the native tokens they send to withdrawing users.
+    pub const MaxNativeRate: FixedU128 = FixedU128::from_inner(1_100_000_000_000_000_000);
+    pub TreasuryAccount: AccountId = PalletId(*b"zp/trsry").into_account();
+}
+
+/// Credits the protocol fees to `TreasuryAccount`.
+pub struct ToTreasury;
+
+impl OnUnbalanced<pallet_balances::NegativeImbalance<Runtime>> for ToTreasury {
+    fn on_nonzero_unbalanced(amount: pallet_balances::NegativeImbalance<Runtime>) {
+        Balances::resolve_creating(&TreasuryAccount::get(), amount);
+    }
 }
 
 impl pallet_zeropool::Config for Runtime {
@@ -298,11 +322,48 @@ impl pallet_zeropool::Config for Runtime {
     type Currency = Balances;
     type InitialOwner = InitialOwner;
     type OperatorManager = ZeropoolOperatorManager;
+    type ComplianceFilter = ();
+    type PauseOrigin = pallet_zeropool::EnsureOwner<Runtime>;
+    type AdminOrigin = frame_system::EnsureRoot<AccountId>;
+    type GuardianOrigin = pallet_zeropool::EnsureOwner<Runtime>;
+    type ForcedTxFee = ForcedTxFee;
+    type ForcedTxInclusionPeriod = ForcedTxInclusionPeriod;
+    type MaxForcedTxSize = MaxForcedTxSize;
+    type RootHistorySize = RootHistorySize;
+    type RewardCurrency = Balances;
+    type RewardAccount = EnergyRewardAccount;
+    type EnergyRewardRate = EnergyRewardRate;
+    type MaxNativeRate = MaxNativeRate;
+    type Treasury = ToTreasury;
+    type WeightInfo = pallet_zeropool::weights::SubstrateWeight<Runtime>;
+    type MaxDirectDepositsPerBatch = MaxDirectDepositsPerBatch;
+    type DirectDepositTimeout = DirectDepositTimeout;
+}
+
+parameter_types! {
+    pub const MaxOperators: u32 = 16;
+    pub const MaxOperatorMetadataLength: u32 = 256;
+    pub const OperatorSlotLength: BlockNumber = 10 * MINUTES;
+    pub const OpenModeTimeout: BlockNumber = MINUTES;
+    pub const OperatorBond: Balance = 1_000_000_000_000_000;
+    pub const OperatorUnbondingDelay: BlockNumber = 7 * DAYS;
+    pub const MissedSlotSlash: Balance = 10_000_000_000_000;
 }
 
 impl pallet_zeropool::operator::Config for Runtime {
     type Event = Event;
-    type InitialOwner = InitialOwner;
+    type OwnerOrigin = pallet_zeropool::EnsureOwner<Runtime>;
+    type MaxOperators = MaxOperators;
+    type MaxMetadataLength = MaxOperatorMetadataLength;
+    type SlotLength = OperatorSlotLength;
+    type OpenModeTimeout = OpenModeTimeout;
+    type Currency = Balances;
+    type OperatorBond = OperatorBond;
+    type UnbondingDelay = OperatorUnbondingDelay;
+    type MissedSlotSlash = MissedSlotSlash;
+    // Slashed funds are burned.
+    type Slash = ();
+    type SlashOrigin = frame_system::EnsureRoot<AccountId>;
 }
 
 // Create the runtime by composing the FRAME pallets that were previously configured.
@@ -484,6 +545,54 @@ impl_runtime_apis! {
         }
     }
 
+    impl pallet_zeropool_runtime_api::ZeropoolApi<Block> for Runtime {
+        fn pool_index() -> sp_core::U256 {
+            pallet_zeropool::PoolIndex::<Runtime>::get()
+        }
+
+        fn messages() -> Vec<pallet_zeropool_runtime_api::Message> {
+            System::events()
+                .into_iter()
+                .filter_map(|record| match (record.phase, record.event) {
+                    (
+                        frame_system::Phase::ApplyExtrinsic(extrinsic_index),
+                        Event::Zeropool(pallet_zeropool::Event::Message(
+                            pool_index,
+                            all_messages_hash,
+                            commitment,
+                            memo,
+                        )),
+                    ) => Some(pallet_zeropool_runtime_api::Message {
+                        extrinsic_index,
+                        // The event carries the pool index after the transaction
+                        index: pool_index.saturating_sub(128u32.into()),
+                        all_messages_hash,
+                        commitment,
+                        memo,
+                    }),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        fn commitments(from_index: sp_core::U256, limit: u32) -> Vec<(sp_core::U256, sp_core::U256, Vec<u8>)> {
+            Zeropool::commitments(from_index, limit)
+        }
+
+        fn ledger() -> pallet_zeropool_runtime_api::Ledger {
+            pallet_zeropool_runtime_api::Ledger {
+                deposited: pallet_zeropool::TotalDeposited::<Runtime>::get(),
+                withdrawn: pallet_zeropool::TotalWithdrawn::<Runtime>::get(),
+                fees: pallet_zeropool::TotalFees::<Runtime>::get(),
+                shielded_supply: pallet_zeropool::ShieldedSupply::<Runtime>::get(),
+            }
+        }
+
+        fn protocol_fee() -> pallet_zeropool_runtime_api::ProtocolFee {
+            pallet_zeropool::ProtocolFees::<Runtime>::get()
+        }
+    }
+
     #[cfg(feature = "runtime-benchmarks")]
     impl frame_benchmarking::Benchmark<Block> for Runtime {
         fn benchmark_metadata(extra: bool) -> (
@@ -546,4 +655,16 @@ impl_runtime_apis! {
             Ok(batches)
         }
     }
+
+    #[cfg(feature = "try-runtime")]
+    impl frame_try_runtime::TryRuntime<Block> for Runtime {
+        fn on_runtime_upgrade() -> (Weight, Weight) {
+            let weight = Executive::try_runtime_upgrade().unwrap();
+            (weight, BlockWeights::get().max_block)
+        }
+
+        fn execute_block_no_check(block: Block) -> Weight {
+            Executive::execute_block_no_check(block)
+        }
+    }
 }